@@ -0,0 +1,22 @@
+; Copy the switches to the LEDs, show 42 on the display and print "Hi" on the console
+; Run with `nano_chip_emulator run examples/devices.asm --memory-map examples/board.map`
+$leds 0xF0
+$switches 0xF1
+$display 0xF2 ; two digits
+$console 0xFF
+
+LD [$switches]
+ST [$leds]
+
+LD 0x66 ; segments of 4
+ST [$display]
+LD 0x5B ; segments of 2
+ST [$display + 1]
+
+LD 'H'
+ST [$console]
+LD 'i'
+ST [$console]
+
+:end
+BRA :end
@@ -0,0 +1,23 @@
+; Multiply a by b with repeated additions
+;@test mul_3_4 ram[$a]=3 ram[$b]=4 expect ram[$result]=12 cycles<200
+;@test mul_by_zero ram[$a]=7 ram[$b]=0 expect ram[$result]=0 z=1
+$a 0      ; Memory address 0
+$b 1      ; Memory address 1, used as the loop counter
+$result 2 ; Memory address 2
+
+LD 0
+ST [$result] ; result = 0
+
+:loop_start
+LD [$b]   ; acc = b
+BZ1 :end  ; stop once b reaches 0
+DEC ACC   ; acc = acc - 1
+ST [$b]   ; b = acc
+
+LD [$result] ; acc = result
+ADD [$a]     ; acc = acc + a
+ST [$result] ; result = acc
+BRA :loop_start
+
+:end
+BRA :end ; halt
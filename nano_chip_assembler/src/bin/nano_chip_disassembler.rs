@@ -0,0 +1,41 @@
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+
+use std::process::ExitCode;
+
+use nano_chip_assembler::disassemble;
+
+fn main() -> ExitCode {
+    let Some(input_file) = std::env::args().nth(1) else {
+        println!("Error, input file is needed as first argument");
+        println!("Usage : nano_chip_disassembler input.o [output.asm]");
+        return ExitCode::FAILURE;
+    };
+
+    let input_bytes = match std::fs::read(&input_file) {
+        Ok(input_bytes) => input_bytes,
+        Err(read_error) => {
+            println!("Error can't read input file : {read_error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let source = match disassemble(&input_bytes) {
+        Ok(source) => source,
+        Err(disassemble_error) => {
+            println!("Error, {disassemble_error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(output_file) = std::env::args().nth(2) {
+        if let Err(write_error) = std::fs::write(output_file, source) {
+            println!("Error, can't write output file : {write_error}");
+            return ExitCode::FAILURE;
+        }
+    } else {
+        print!("{source}");
+    }
+
+    ExitCode::SUCCESS
+}
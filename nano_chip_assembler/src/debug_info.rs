@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::program::{Program, Symbol, SymbolKind};
+
+/// Version written in the first line of debug info files, other versions are rejected
+pub const DEBUG_INFO_VERSION: u32 = 1;
+
+/// First word of debug info files
+const MAGIC: &str = "nano_chip_debug";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugInfoError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DebugInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} : {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for DebugInfoError {}
+
+/// Symbols and source positions of an assembled program, the sidecar file that lets the emulator
+/// show names for a binary program
+///
+/// ```text
+/// nano_chip_debug 1
+/// source /code/multiply.asm
+/// rom 11 EF6FF1E5302004B5
+/// constant a 0x00 4
+/// label loop_start 0x02 11
+/// line 0x00 8
+/// line 0x01 9
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// Path of the source file, as given when writing
+    pub source: String,
+    /// Number of words of the program
+    pub words: usize,
+    /// `nano_chip_isa::rom_hash` of the program, to reject debug info written for another one
+    pub rom_hash: u64,
+    /// Constants and labels with the line of their definition
+    pub symbols: Vec<Symbol>,
+    /// Source line of each ROM word, by address
+    pub lines: Vec<usize>,
+}
+
+impl Program {
+    /// Debug info of the program assembled from `source_path`
+    pub fn debug_info(&self, source_path: &str) -> DebugInfo {
+        DebugInfo {
+            source: source_path.to_owned(),
+            words: self.words().len(),
+            rom_hash: nano_chip_isa::rom_hash(self.words()),
+            symbols: self.symbols().to_vec(),
+            lines: self
+                .source_lines()
+                .iter()
+                .map(|source_line| source_line.line)
+                .collect(),
+        }
+    }
+}
+
+/// Parse a byte written as `0x2A`
+fn hex_byte(text: &str) -> Option<u8> {
+    u8::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+impl DebugInfo {
+    /// Read the text written by `Display`
+    pub fn parse(text: &str) -> Result<Self, DebugInfoError> {
+        let mut version = false;
+        let mut source = None;
+        let mut rom = None;
+        let mut symbols = Vec::new();
+        let mut lines = Vec::new();
+
+        for (line_n, line) in (1..).zip(text.lines()) {
+            let error = |message: String| DebugInfoError {
+                line: line_n,
+                message,
+            };
+
+            let Some((key, rest)) = line.trim().split_once(char::is_whitespace) else {
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                return Err(error(format!("unexpected `{}`", line.trim())));
+            };
+
+            if !version {
+                let expected = (MAGIC, DEBUG_INFO_VERSION.to_string());
+
+                if (key, rest.trim().to_owned()) != expected {
+                    return Err(error(format!("expected `{MAGIC} {DEBUG_INFO_VERSION}`")));
+                }
+
+                version = true;
+                continue;
+            }
+
+            let values = rest.split_whitespace().collect::<Vec<_>>();
+
+            match (key, values.as_slice()) {
+                // The path may hold spaces, it takes the rest of the line
+                ("source", _) => source = Some(rest.trim().to_owned()),
+                ("rom", [words, hash]) => {
+                    rom = Some((
+                        words
+                            .parse()
+                            .map_err(|_| error(format!("invalid word count `{words}`")))?,
+                        u64::from_str_radix(hash, 16)
+                            .map_err(|_| error(format!("invalid hash `{hash}`")))?,
+                    ));
+                }
+                ("constant" | "label", [name, value, definition]) => {
+                    symbols.push(Symbol {
+                        name: (*name).to_owned(),
+                        kind: if key == "label" {
+                            SymbolKind::Label
+                        } else {
+                            SymbolKind::Constant
+                        },
+                        value: hex_byte(value)
+                            .ok_or_else(|| error(format!("invalid value `{value}`")))?,
+                        line: definition
+                            .parse()
+                            .map_err(|_| error(format!("invalid line `{definition}`")))?,
+                    });
+                }
+                ("line", [address, source_line]) => {
+                    if hex_byte(address).map(usize::from) != Some(lines.len()) {
+                        return Err(error(format!(
+                            "expected address 0x{:02X}, got `{address}`",
+                            lines.len()
+                        )));
+                    }
+
+                    lines.push(
+                        source_line
+                            .parse()
+                            .map_err(|_| error(format!("invalid line `{source_line}`")))?,
+                    );
+                }
+                _ => return Err(error(format!("unexpected `{}`", line.trim()))),
+            }
+        }
+
+        let end = text.lines().count();
+        let missing = |field: &str| DebugInfoError {
+            line: end,
+            message: format!("missing {field}"),
+        };
+
+        if !version {
+            return Err(missing("version"));
+        }
+
+        let source = source.ok_or_else(|| missing("source"))?;
+        let (words, rom_hash) = rom.ok_or_else(|| missing("rom"))?;
+
+        Ok(Self {
+            source,
+            words,
+            rom_hash,
+            symbols,
+            lines,
+        })
+    }
+}
+
+impl fmt::Display for DebugInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MAGIC} {DEBUG_INFO_VERSION}")?;
+        writeln!(f, "source {}", self.source)?;
+        writeln!(f, "rom {} {:016X}", self.words, self.rom_hash)?;
+
+        for symbol in &self.symbols {
+            let kind = match symbol.kind {
+                SymbolKind::Constant => "constant",
+                SymbolKind::Label => "label",
+            };
+
+            writeln!(
+                f,
+                "{kind} {} 0x{:02X} {}",
+                symbol.name, symbol.value, symbol.line
+            )?;
+        }
+
+        for (address, line) in self.lines.iter().enumerate() {
+            writeln!(f, "line 0x{address:02X} {line}")?;
+        }
+
+        Ok(())
+    }
+}
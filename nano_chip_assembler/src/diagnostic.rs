@@ -0,0 +1,80 @@
+use std::fmt::Write;
+
+use crate::error::{AssembleError, ErrorKind};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+
+/// Wraps text in an ANSI style when colours are enabled
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{style}{text}{RESET}")
+    } else {
+        text.to_owned()
+    }
+}
+
+impl AssembleError {
+    /// Format the error like rustc does : message, file position, source line and a caret under the token
+    pub fn render(&self, file_name: &str, source: &str, color: bool) -> String {
+        let span = self.span();
+        // Line and column 0 are only met in errors built by hand, they show no source
+        let source_line = span
+            .line
+            .checked_sub(1)
+            .and_then(|index| source.lines().nth(index))
+            .unwrap_or_default();
+        let gutter = " ".repeat(span.line.to_string().len());
+
+        // Tabs are kept so that the caret stays aligned with the token
+        let padding = source_line
+            .chars()
+            .take(span.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let mut rendered = String::new();
+
+        let _ = writeln!(
+            rendered,
+            "{}{}",
+            paint("error", BOLD_RED, color),
+            paint(&format!(": {}", self.kind), BOLD, color)
+        );
+        let _ = writeln!(
+            rendered,
+            "{gutter}{} {file_name}:{}:{}",
+            paint("-->", BOLD_BLUE, color),
+            span.line,
+            span.column
+        );
+        let _ = writeln!(rendered, "{gutter} {}", paint("|", BOLD_BLUE, color));
+        let _ = writeln!(
+            rendered,
+            "{} {source_line}",
+            paint(&format!("{} |", span.line), BOLD_BLUE, color)
+        );
+        let _ = writeln!(
+            rendered,
+            "{gutter} {} {padding}{}",
+            paint("|", BOLD_BLUE, color),
+            paint(&"^".repeat(span.length.max(1)), BOLD_RED, color)
+        );
+
+        if let ErrorKind::DuplicateConstant(previous) | ErrorKind::DuplicateLabel(previous) =
+            self.kind
+        {
+            let _ = writeln!(
+                rendered,
+                "{gutter} {} first defined at {file_name}:{}:{}",
+                paint("= note:", BOLD_BLUE, color),
+                previous.line,
+                previous.column
+            );
+        }
+
+        rendered
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+
+use nano_chip_isa::{ROM_SIZE, WORD_MASK};
+
+use crate::syntax_tree::WORD_DIRECTIVE;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DisassembleError {
+    /// Words are stored on two bytes, holds the length of the image
+    OddLength(usize),
+    /// Holds the length of the image
+    TooLarge(usize),
+}
+
+impl fmt::Display for DisassembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OddLength(length) => write!(
+                f,
+                "ROM image has {length} bytes, an image holds 2 bytes per word"
+            ),
+            Self::TooLarge(length) => write!(
+                f,
+                "ROM image has {length} bytes, the ROM can hold at most {} bytes",
+                ROM_SIZE * 2
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DisassembleError {}
+
+/// Name of the label synthesized for a branch target
+fn label_name(address: usize) -> String {
+    format!(":L{address:02X}")
+}
+
+/// Turn a ROM image back into source code, assembling the result gives the same image
+///
+/// Branch targets get a `:Lxx` label and words that aren't a valid instruction
+/// are written as `.word` directives
+pub fn disassemble(bytes: &[u8]) -> Result<String, DisassembleError> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(DisassembleError::OddLength(bytes.len()));
+    }
+
+    if bytes.len() > ROM_SIZE * 2 {
+        return Err(DisassembleError::TooLarge(bytes.len()));
+    }
+
+    let words = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+
+    // Only the words that encode back to themselves can be written as instructions
+    let instructions = words
+        .iter()
+        .map(|&word| {
+            nano_chip_isa::decode(word).filter(|(info, operand)| info.encode(*operand) == word)
+        })
+        .collect::<Vec<_>>();
+
+    // A label can be placed before any instruction or after the last one
+    let labels = instructions
+        .iter()
+        .flatten()
+        .filter(|(info, _)| info.mnemonic.is_branch())
+        .map(|(_, operand)| usize::from(*operand))
+        .filter(|&target| target <= words.len())
+        .collect::<BTreeSet<_>>();
+
+    let mut source = String::new();
+
+    for (address, (word, instruction)) in words.iter().zip(&instructions).enumerate() {
+        if labels.contains(&address) {
+            let _ = writeln!(source, "{}", label_name(address));
+        }
+
+        match instruction {
+            Some((info, operand))
+                if info.mnemonic.is_branch() && labels.contains(&usize::from(*operand)) =>
+            {
+                let _ = writeln!(
+                    source,
+                    "{} {}",
+                    info.mnemonic.name(),
+                    label_name(usize::from(*operand))
+                );
+            }
+            Some((info, operand)) => {
+                let _ = writeln!(source, "{}", info.format(*operand));
+            }
+            None => {
+                let reason = match nano_chip_isa::decode(*word) {
+                    Some((info, _)) => format!("{} with unused bits set", info.mnemonic.name()),
+                    None => format!("unknown opcode 0x{:02X}", (word & WORD_MASK) >> 8),
+                };
+
+                let _ = writeln!(source, "{WORD_DIRECTIVE} 0x{word:04X} ; {reason}");
+            }
+        }
+    }
+
+    if labels.contains(&words.len()) {
+        let _ = writeln!(source, "{}", label_name(words.len()));
+    }
+
+    Ok(source)
+}
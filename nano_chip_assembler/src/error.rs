@@ -0,0 +1,154 @@
+use std::fmt;
+
+use nano_chip_isa::Mnemonic;
+
+use crate::literal::{MAX_BYTE, MIN_BYTE};
+use crate::span::Span;
+use crate::syntax_tree::{MAX_INSTRUCTIONS, WORD_DIRECTIVE};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnknownInstruction,
+    MissingConstantValue,
+    ConstantOutOfRange,
+    ValueOutOfRange,
+    InvalidNumber,
+    InvalidCharacter,
+    MissingClosingBracket,
+    UnexpectedToken,
+    /// An operand ended where a number, constant or label was expected
+    ExpectedValue,
+    MissingClosingParenthesis,
+    InvalidName,
+    DivisionByZero,
+    InvalidShift,
+    /// A constant is defined using its own value
+    CircularConstant,
+    /// Holds the position of the first definition
+    DuplicateConstant(Span),
+    /// Holds the position of the first definition
+    DuplicateLabel(Span),
+    IndirectLabel,
+    /// `.word` needs a direct value that fits in 16 bits
+    InvalidWord,
+    TooManyInstructions,
+    /// A label after the last ROM address
+    LabelPastEnd,
+    UnknownConstant,
+    UnknownLabel,
+    UnexpectedParameter(Mnemonic),
+    /// A `;@test` annotation without a test name
+    MissingTestName,
+    /// The instruction has no variant for the addressing mode that was used
+    WrongAddressingMode(Mnemonic),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownInstruction => write!(f, "Unknown instruction name"),
+            Self::MissingConstantValue => {
+                write!(f, "You must specify a value after declaring a constant")
+            }
+            Self::ConstantOutOfRange => write!(
+                f,
+                "Constant values must fit in 8 bits, between {MIN_BYTE} and {MAX_BYTE}"
+            ),
+            Self::ValueOutOfRange => write!(
+                f,
+                "Values must fit in 8 bits, between {MIN_BYTE} and {MAX_BYTE}"
+            ),
+            Self::InvalidNumber => write!(f, "Can't parse number"),
+            Self::InvalidCharacter => write!(
+                f,
+                "A character literal holds a single ASCII character, like 'A' or '\\n'"
+            ),
+            Self::MissingClosingBracket => {
+                write!(f, "Can't parse parameter, did you forget a closing ] ?")
+            }
+            Self::UnexpectedToken => write!(f, "Unexpected token in expression"),
+            Self::ExpectedValue => write!(f, "Expected a number, a constant or a label"),
+            Self::MissingClosingParenthesis => {
+                write!(f, "Can't parse expression, did you forget a closing ) ?")
+            }
+            Self::InvalidName => write!(
+                f,
+                "Names can only contain ASCII letters, digits and underscores"
+            ),
+            Self::DivisionByZero => write!(f, "Division by zero"),
+            Self::InvalidShift => write!(f, "Shift amounts must be between 0 and 63"),
+            Self::CircularConstant => write!(f, "Constant depends on its own value"),
+            Self::DuplicateConstant(_) => write!(f, "A constant with this name already exists"),
+            Self::DuplicateLabel(_) => write!(f, "A label with this name already exists"),
+            Self::IndirectLabel => write!(f, "Indirect addressing on labels is not allowed"),
+            Self::InvalidWord => write!(f, "{WORD_DIRECTIVE} takes a value between 0 and 0xFFFF"),
+            Self::TooManyInstructions => write!(
+                f,
+                "Too many instructions, a program can contain at most {MAX_INSTRUCTIONS} instructions"
+            ),
+            Self::LabelPastEnd => write!(
+                f,
+                "Label past the end of the ROM, it must be preceded by at most {} instructions",
+                MAX_INSTRUCTIONS - 1
+            ),
+            Self::UnknownConstant => write!(f, "Constant doesn't exist"),
+            Self::UnknownLabel => write!(f, "Label doesn't exist"),
+            Self::UnexpectedParameter(mnemonic) => {
+                write!(f, "{} instruction takes no parameter", mnemonic.name())
+            }
+            Self::MissingTestName => write!(f, "A test annotation starts with the test name"),
+            Self::WrongAddressingMode(mnemonic) => {
+                let supported_modes = nano_chip_isa::variants(*mnemonic)
+                    .map(|info| info.mode.description())
+                    .collect::<Vec<_>>()
+                    .join(" or ");
+
+                write!(f, "{} takes {supported_modes} as parameter", mnemonic.name())
+            }
+        }
+    }
+}
+
+/// An error found while assembling, located on the offending token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column of the first character of the token, starting at 1
+    pub column: usize,
+    pub token: String,
+    pub kind: ErrorKind,
+}
+
+impl AssembleError {
+    pub fn new(line: usize, column: usize, token: &str, kind: ErrorKind) -> Self {
+        Self {
+            line,
+            column,
+            token: token.to_owned(),
+            kind,
+        }
+    }
+
+    /// Error on the token found at the given position
+    pub fn at(span: Span, token: &str, kind: ErrorKind) -> Self {
+        Self::new(span.line, span.column, token, kind)
+    }
+
+    /// Position of the offending token
+    pub fn span(&self) -> Span {
+        Span::new(self.line, self.column, self.token.chars().count())
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Error line {}, column {} : {} : `{}`",
+            self.line, self.column, self.kind, self.token
+        )
+    }
+}
+
+impl std::error::Error for AssembleError {}
@@ -0,0 +1,385 @@
+use crate::error::{AssembleError, ErrorKind};
+use crate::literal::parse_literal;
+use crate::span::Span;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    /// Complement of the low 8 bits, so that `~0xFF` is 0
+    Not,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+impl BinaryOp {
+    /// Operators with a higher precedence bind tighter, same order as in C
+    const fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::Xor => 2,
+            Self::And => 3,
+            Self::Shl | Self::Shr => 4,
+            Self::Add | Self::Sub => 5,
+            Self::Mul | Self::Div | Self::Rem => 6,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExprKind {
+    Number(i64),
+    Const(String),
+    Label(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// An operand or constant value, evaluated once every label is known
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+    /// Source text of the expression, used in errors
+    pub text: String,
+}
+
+impl Expr {
+    fn error(&self, kind: ErrorKind) -> AssembleError {
+        AssembleError::at(self.span, &self.text, kind)
+    }
+
+    pub fn references_label(&self) -> bool {
+        match &self.kind {
+            ExprKind::Number(_) | ExprKind::Const(_) => false,
+            ExprKind::Label(_) => true,
+            ExprKind::Unary(_, operand) => operand.references_label(),
+            ExprKind::Binary(_, lhs, rhs) => lhs.references_label() || rhs.references_label(),
+        }
+    }
+
+    /// Compute the value of the expression, `symbol_value` gives the value of constants and labels
+    ///
+    /// Returns None if the expression can't be evaluated, the reasons are added to `errors`
+    pub fn evaluate<F>(&self, symbol_value: &mut F, errors: &mut Vec<AssembleError>) -> Option<i64>
+    where
+        F: FnMut(&Self, &mut Vec<AssembleError>) -> Option<i64>,
+    {
+        match &self.kind {
+            ExprKind::Number(value) => Some(*value),
+            ExprKind::Const(_) | ExprKind::Label(_) => symbol_value(self, errors),
+            ExprKind::Unary(op, operand) => {
+                let value = operand.evaluate(symbol_value, errors)?;
+
+                match op {
+                    UnaryOp::Neg => value.checked_neg().or_else(|| {
+                        errors.push(self.error(ErrorKind::ValueOutOfRange));
+                        None
+                    }),
+                    UnaryOp::Not => Some(!value & 0xFF),
+                }
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                // Both sides are evaluated so that every error is reported
+                let lhs_value = lhs.evaluate(symbol_value, errors);
+                let rhs_value = rhs.evaluate(symbol_value, errors);
+                let (a, b) = (lhs_value?, rhs_value?);
+
+                let result = match op {
+                    BinaryOp::Add => a.checked_add(b),
+                    BinaryOp::Sub => a.checked_sub(b),
+                    BinaryOp::Mul => a.checked_mul(b),
+                    BinaryOp::Div | BinaryOp::Rem if b == 0 => {
+                        errors.push(rhs.error(ErrorKind::DivisionByZero));
+                        return None;
+                    }
+                    BinaryOp::Div => a.checked_div(b),
+                    BinaryOp::Rem => a.checked_rem(b),
+                    BinaryOp::And => Some(a & b),
+                    BinaryOp::Or => Some(a | b),
+                    BinaryOp::Xor => Some(a ^ b),
+                    BinaryOp::Shl | BinaryOp::Shr if !(0..64).contains(&b) => {
+                        errors.push(rhs.error(ErrorKind::InvalidShift));
+                        return None;
+                    }
+                    BinaryOp::Shl => u32::try_from(b).ok().and_then(|b| a.checked_shl(b)),
+                    BinaryOp::Shr => Some(a >> b),
+                };
+
+                if result.is_none() {
+                    errors.push(self.error(ErrorKind::ValueOutOfRange));
+                }
+
+                result
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Number(i64),
+    Const,
+    Label,
+    Unary(UnaryOp),
+    Binary(BinaryOp),
+    OpenParenthesis,
+    CloseParenthesis,
+}
+
+#[derive(Clone, Copy)]
+struct Token {
+    kind: TokenKind,
+    start: usize,
+    end: usize,
+}
+
+/// Characters allowed in constant and label names
+pub const fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+struct ExpressionParser<'a> {
+    line_n: usize,
+    /// Code part of the line, positions are byte indexes in this text
+    code: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    end: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(
+            self.line_n,
+            self.code[..start].chars().count() + 1,
+            self.code[start..end].chars().count(),
+        )
+    }
+
+    fn error(&self, start: usize, end: usize, kind: ErrorKind) -> AssembleError {
+        AssembleError::at(self.span(start, end), &self.code[start..end], kind)
+    }
+
+    fn expr(&self, kind: ExprKind, start: usize, end: usize) -> Expr {
+        Expr {
+            kind,
+            span: self.span(start, end),
+            text: self.code[start..end].to_owned(),
+        }
+    }
+
+    /// Split the text between `start` and `end` in tokens
+    fn tokenize(&mut self, start: usize) -> Result<(), AssembleError> {
+        let text = &self.code[..self.end];
+        let mut position = start;
+
+        while let Some(c) = text[position..].chars().next() {
+            let rest = &text[position..];
+            let first_len = c.len_utf8();
+
+            // End of the token made of the first character and every following one matching the predicate
+            let run_end = |predicate: fn(char) -> bool| {
+                position
+                    + first_len
+                    + rest[first_len..]
+                        .find(|next| !predicate(next))
+                        .unwrap_or(rest.len() - first_len)
+            };
+
+            let (kind, token_end) = match c {
+                _ if c.is_whitespace() => {
+                    position += first_len;
+                    continue;
+                }
+                '+' => (TokenKind::Binary(BinaryOp::Add), position + 1),
+                '-' => (TokenKind::Binary(BinaryOp::Sub), position + 1),
+                '*' => (TokenKind::Binary(BinaryOp::Mul), position + 1),
+                '/' => (TokenKind::Binary(BinaryOp::Div), position + 1),
+                '%' => (TokenKind::Binary(BinaryOp::Rem), position + 1),
+                '&' => (TokenKind::Binary(BinaryOp::And), position + 1),
+                '|' => (TokenKind::Binary(BinaryOp::Or), position + 1),
+                '^' => (TokenKind::Binary(BinaryOp::Xor), position + 1),
+                '~' => (TokenKind::Unary(UnaryOp::Not), position + 1),
+                '(' => (TokenKind::OpenParenthesis, position + 1),
+                ')' => (TokenKind::CloseParenthesis, position + 1),
+                '<' if rest.starts_with("<<") => (TokenKind::Binary(BinaryOp::Shl), position + 2),
+                '>' if rest.starts_with(">>") => (TokenKind::Binary(BinaryOp::Shr), position + 2),
+                '$' | ':' => {
+                    let end = run_end(is_name_char);
+
+                    if end == position + 1 {
+                        return Err(self.error(position, end, ErrorKind::InvalidName));
+                    }
+
+                    let kind = if c == '$' {
+                        TokenKind::Const
+                    } else {
+                        TokenKind::Label
+                    };
+
+                    (kind, end)
+                }
+                '\'' => {
+                    let end = position + char_literal_length(rest);
+                    (self.number(position, end)?, end)
+                }
+                _ if c.is_ascii_digit() => {
+                    let end = run_end(is_name_char);
+                    (self.number(position, end)?, end)
+                }
+                _ => {
+                    let end =
+                        run_end(|next| !next.is_whitespace() && !"+-*/%&|^~()<>".contains(next));
+                    return Err(self.error(position, end, ErrorKind::UnexpectedToken));
+                }
+            };
+
+            self.tokens.push(Token {
+                kind,
+                start: position,
+                end: token_end,
+            });
+
+            position = token_end;
+        }
+
+        Ok(())
+    }
+
+    fn number(&self, start: usize, end: usize) -> Result<TokenKind, AssembleError> {
+        match parse_literal(&self.code[start..end]) {
+            Some(Ok(value)) => Ok(TokenKind::Number(value)),
+            Some(Err(kind)) => Err(self.error(start, end, kind)),
+            None => Err(self.error(start, end, ErrorKind::UnexpectedToken)),
+        }
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.position).copied()
+    }
+
+    /// Error on the next token, or right after the expression if there is none
+    fn unexpected(&self, kind: ErrorKind) -> AssembleError {
+        let (start, end) = self
+            .peek()
+            .map_or((self.end, self.end), |token| (token.start, token.end));
+
+        self.error(start, end, kind)
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, AssembleError> {
+        let start = self.peek().map_or(self.end, |token| token.start);
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(Token {
+            kind: TokenKind::Binary(op),
+            ..
+        }) = self.peek()
+        {
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            self.position += 1;
+            let rhs = self.parse_binary(op.precedence() + 1)?;
+            let end = self.tokens[self.position - 1].end;
+
+            lhs = self.expr(
+                ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+                start,
+                end,
+            );
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, AssembleError> {
+        let Some(token) = self.peek() else {
+            return Err(self.unexpected(ErrorKind::ExpectedValue));
+        };
+
+        self.position += 1;
+
+        let kind = match token.kind {
+            TokenKind::Number(value) => ExprKind::Number(value),
+            TokenKind::Const => ExprKind::Const(self.code[token.start + 1..token.end].to_owned()),
+            TokenKind::Label => ExprKind::Label(self.code[token.start + 1..token.end].to_owned()),
+            TokenKind::Unary(op) => ExprKind::Unary(op, Box::new(self.parse_unary()?)),
+            TokenKind::Binary(BinaryOp::Sub) => {
+                ExprKind::Unary(UnaryOp::Neg, Box::new(self.parse_unary()?))
+            }
+            TokenKind::OpenParenthesis => {
+                let inner = self.parse_binary(0)?;
+
+                if self.peek().map(|token| token.kind) != Some(TokenKind::CloseParenthesis) {
+                    return Err(self.unexpected(ErrorKind::MissingClosingParenthesis));
+                }
+
+                self.position += 1;
+
+                inner.kind
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected(ErrorKind::ExpectedValue));
+            }
+        };
+
+        let end = self.tokens[self.position - 1].end;
+
+        Ok(self.expr(kind, token.start, end))
+    }
+}
+
+/// Length in bytes of the character literal at the start of `text`, up to the closing quote
+fn char_literal_length(text: &str) -> usize {
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices().skip(1) {
+        if c == '\'' && !escaped {
+            return i + 1;
+        }
+        escaped = c == '\\' && !escaped;
+    }
+
+    text.len()
+}
+
+/// Parse the expression found in `code` between the byte indexes `start` and `end`
+pub fn parse_expression(
+    line_n: usize,
+    code: &str,
+    start: usize,
+    end: usize,
+) -> Result<Expr, AssembleError> {
+    let mut parser = ExpressionParser {
+        line_n,
+        code,
+        tokens: Vec::new(),
+        position: 0,
+        end,
+    };
+
+    parser.tokenize(start)?;
+
+    let expr = parser.parse_binary(0)?;
+
+    if parser.peek().is_some() {
+        Err(parser.unexpected(ErrorKind::UnexpectedToken))
+    } else {
+        Ok(expr)
+    }
+}
@@ -0,0 +1,38 @@
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![allow(clippy::too_many_lines)]
+#![allow(clippy::cast_lossless)]
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::match_wildcard_for_single_variants)]
+#![allow(clippy::option_if_let_else)]
+#![allow(clippy::must_use_candidate)]
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::struct_field_names)]
+
+mod debug_info;
+mod diagnostic;
+mod disassembler;
+mod error;
+mod expression;
+mod instruction_generator;
+mod listing;
+mod literal;
+mod parser;
+mod program;
+mod span;
+mod syntax_tree;
+mod tests;
+
+pub use debug_info::{DebugInfo, DebugInfoError, DEBUG_INFO_VERSION};
+pub use disassembler::{disassemble, DisassembleError};
+pub use error::{AssembleError, ErrorKind};
+pub use literal::{parse_literal, to_byte};
+pub use program::{Program, SourceLine, Symbol, SymbolKind, TestAnnotation};
+pub use span::Span;
+
+/// Assemble source code into a program
+///
+/// Assembly goes on after an error so that every error of the source is returned, sorted by position
+pub fn assemble(source: &str) -> Result<Program, Vec<AssembleError>> {
+    parser::parse(source)
+}
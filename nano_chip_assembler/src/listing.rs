@@ -0,0 +1,83 @@
+use std::fmt::Write;
+
+use crate::program::{Program, SymbolKind};
+
+impl Program {
+    /// Human readable listing : for each instruction its ROM address, its encoding in hexadecimal
+    /// and binary and the source line it comes from, followed by the symbol table
+    pub fn listing(&self) -> String {
+        let mut listing = String::new();
+
+        let line_width = self
+            .source_lines()
+            .iter()
+            .map(|source_line| source_line.line.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max("Line".len());
+
+        let _ = writeln!(
+            listing,
+            "Addr  Hex   Binary           {:>line_width$}  Source",
+            "Line"
+        );
+
+        for (address, word) in self.words().iter().enumerate() {
+            let _ = write!(
+                listing,
+                "0x{address:02X}  {word:04X}  {:06b} {:08b}  ",
+                word >> 8,
+                word & 0xFF
+            );
+
+            match self.source_lines().get(address) {
+                Some(source_line) => {
+                    let _ = writeln!(
+                        listing,
+                        "{:>line_width$}  {}",
+                        source_line.line,
+                        source_line.text.trim()
+                    );
+                }
+                None => {
+                    let _ = writeln!(listing, "{:>line_width$}", "");
+                }
+            }
+        }
+
+        if !self.symbols().is_empty() {
+            let name_width = self
+                .symbols()
+                .iter()
+                .map(|symbol| symbol.prefixed_name().chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("Symbol".len());
+
+            let _ = writeln!(listing);
+            let _ = writeln!(
+                listing,
+                "{:<name_width$}  Kind      Value     Line",
+                "Symbol"
+            );
+
+            for symbol in self.symbols() {
+                let kind = match symbol.kind {
+                    SymbolKind::Constant => "constant",
+                    SymbolKind::Label => "label",
+                };
+
+                let _ = writeln!(
+                    listing,
+                    "{:<name_width$}  {kind:<8}  0x{:02X} {:>3}  {:>4}",
+                    symbol.prefixed_name(),
+                    symbol.value,
+                    symbol.value,
+                    symbol.line
+                );
+            }
+        }
+
+        listing
+    }
+}
@@ -0,0 +1,87 @@
+use crate::error::ErrorKind;
+
+/// Smallest value that fits in a byte, negative values are stored as two's complement
+pub const MIN_BYTE: i64 = -128;
+
+/// Biggest value that fits in a byte
+pub const MAX_BYTE: i64 = 255;
+
+/// Parse a numeric literal : decimal, `0x` hexadecimal, `0b` binary or a character between quotes
+///
+/// Digits can be separated by underscores and decimal numbers can be negative.
+/// Returns None if the text doesn't start like a number.
+pub fn parse_literal(text: &str) -> Option<Result<i64, ErrorKind>> {
+    if let Some(quoted) = text.strip_prefix('\'') {
+        return Some(parse_char(quoted));
+    }
+
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text),
+    };
+
+    if !unsigned.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (radix, digits) = if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        (16, hex)
+    } else if let Some(binary) = unsigned
+        .strip_prefix("0b")
+        .or_else(|| unsigned.strip_prefix("0B"))
+    {
+        (2, binary)
+    } else {
+        (10, unsigned)
+    };
+
+    let digits = digits.replace('_', "");
+
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Some(Err(ErrorKind::InvalidNumber));
+    }
+
+    // Every digit is valid so parsing can only fail on overflow
+    Some(
+        i64::from_str_radix(&digits, radix)
+            .map(|value| if negative { -value } else { value })
+            .map_err(|_| ErrorKind::ValueOutOfRange),
+    )
+}
+
+/// Parse what follows the opening quote of a character literal, only ASCII characters are allowed
+fn parse_char(quoted: &str) -> Result<i64, ErrorKind> {
+    let content = quoted
+        .strip_suffix('\'')
+        .ok_or(ErrorKind::InvalidCharacter)?;
+
+    let c = match content {
+        "\\n" => '\n',
+        "\\t" => '\t',
+        "\\0" => '\0',
+        "\\\\" => '\\',
+        "\\'" => '\'',
+        _ => {
+            let mut chars = content.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c != '\\' && c.is_ascii() => c,
+                _ => return Err(ErrorKind::InvalidCharacter),
+            }
+        }
+    };
+
+    Ok(i64::from(u32::from(c)))
+}
+
+/// Convert a value to the byte stored in the ROM, negative values use two's complement
+pub fn to_byte(value: i64) -> Option<u8> {
+    if (MIN_BYTE..=MAX_BYTE).contains(&value) {
+        u8::try_from(value.rem_euclid(256)).ok()
+    } else {
+        None
+    }
+}
@@ -1,52 +1,138 @@
 #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
-#![allow(clippy::too_many_lines)]
-#![allow(clippy::cast_lossless)]
-#![allow(clippy::cast_possible_truncation)]
-#![allow(clippy::match_wildcard_for_single_variants)]
-#![allow(clippy::option_if_let_else)]
-
-mod instruction_generator;
-mod parser;
-mod syntax_tree;
-mod tests;
-
-fn main() {
-    if let Some(input_file) = std::env::args().nth(1) {
-        if let Some(output_file) = std::env::args().nth(2) {
-            match std::fs::read_to_string(input_file) {
-                Ok(input_str) => match parser::parse(&input_str) {
-                    Ok(binary_u16) => {
-                        let mut binary_u8 = Vec::<u8>::new();
-
-                        for n in binary_u16 {
-                            binary_u8.push((n >> 8) as u8);
-                            binary_u8.push((n & 0xFF) as u8);
-                        }
-
-                        match std::fs::write(output_file, binary_u8) {
-                            Ok(()) => {
-                                println!("Assembly successfull !");
-                            }
-
-                            Err(write_error) => {
-                                println!("Error, can't write output file : {write_error}");
-                            }
-                        }
+
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+use nano_chip_assembler::assemble;
+
+const USAGE: &str =
+    "Usage : nano_chip_assembler input.asm output.o [--listing output.lst] [--tests output.test] [--debug-info output.dbg]";
+
+/// Command line arguments
+struct Arguments {
+    input: String,
+    output: String,
+    listing: Option<String>,
+    /// Test manifest collecting the `;@test` annotations
+    tests: Option<String>,
+    /// Symbols and source lines for the emulator
+    debug_info: Option<String>,
+}
+
+fn parse_arguments() -> Result<Arguments, String> {
+    let mut positional = Vec::new();
+    let mut listing_file = None;
+    let mut tests_file = None;
+    let mut debug_info_file = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listing" => {
+                listing_file = Some(args.next().ok_or("Error, --listing needs a file name")?);
+            }
+            "--tests" => {
+                tests_file = Some(args.next().ok_or("Error, --tests needs a file name")?);
+            }
+            "--debug-info" => {
+                debug_info_file = Some(args.next().ok_or("Error, --debug-info needs a file name")?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("Error, unknown option {arg}")),
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+
+    let input_file = positional
+        .next()
+        .ok_or("Error, input file is needed as first argument")?;
+    let output_file = positional
+        .next()
+        .ok_or("Error, output file is needed as second argument")?;
+
+    if let Some(extra) = positional.next() {
+        return Err(format!("Error, unexpected argument {extra}"));
+    }
+
+    Ok(Arguments {
+        input: input_file,
+        output: output_file,
+        listing: listing_file,
+        tests: tests_file,
+        debug_info: debug_info_file,
+    })
+}
+
+fn main() -> ExitCode {
+    let arguments = match parse_arguments() {
+        Ok(arguments) => arguments,
+        Err(message) => {
+            println!("{message}");
+            println!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let input_file = &arguments.input;
+
+    match std::fs::read_to_string(input_file) {
+        Ok(input_str) => match assemble(&input_str) {
+            Ok(program) => {
+                if let Err(write_error) = std::fs::write(&arguments.output, program.to_bytes()) {
+                    println!("Error, can't write output file : {write_error}");
+                    return ExitCode::FAILURE;
+                }
+
+                if let Some(listing_file) = &arguments.listing {
+                    if let Err(write_error) = std::fs::write(listing_file, program.listing()) {
+                        println!("Error, can't write listing file : {write_error}");
+                        return ExitCode::FAILURE;
+                    }
+                }
+
+                // The manifest and debug info can be moved around, so they point to the source with
+                // an absolute path
+                let source_path = std::fs::canonicalize(input_file)
+                    .map_or_else(|_| input_file.clone(), |path| path.display().to_string());
+
+                if let Some(tests_file) = &arguments.tests {
+                    if let Err(write_error) =
+                        std::fs::write(tests_file, program.test_manifest(&source_path))
+                    {
+                        println!("Error, can't write test manifest : {write_error}");
+                        return ExitCode::FAILURE;
                     }
+                }
+
+                if let Some(debug_info_file) = &arguments.debug_info {
+                    let debug_info = program.debug_info(&source_path).to_string();
 
-                    Err(parse_error) => {
-                        println!("{parse_error}");
+                    if let Err(write_error) = std::fs::write(debug_info_file, debug_info) {
+                        println!("Error, can't write debug info : {write_error}");
+                        return ExitCode::FAILURE;
                     }
-                },
-                Err(read_error) => {
-                    println!("Error can't read input file : {read_error}");
                 }
+
+                println!("Assembly successfull !");
+                return ExitCode::SUCCESS;
             }
-        } else {
-            println!("Error, output file is needed as second argument");
+
+            Err(assemble_errors) => {
+                let color = std::io::stdout().is_terminal();
+
+                for assemble_error in &assemble_errors {
+                    println!("{}", assemble_error.render(input_file, &input_str, color));
+                }
+
+                println!("Assembly failed with {} error(s)", assemble_errors.len());
+            }
+        },
+        Err(read_error) => {
+            println!("Error can't read input file : {read_error}");
         }
-    } else {
-        println!("Error, input file is needed as first argument");
     }
+
+    ExitCode::FAILURE
 }
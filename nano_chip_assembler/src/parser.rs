@@ -1,160 +1,301 @@
+use nano_chip_isa::Mnemonic;
+
+use crate::error::{AssembleError, ErrorKind};
+use crate::expression::{is_name_char, parse_expression};
 use crate::instruction_generator::generate_instruction;
+use crate::program::{Program, SourceLine, TestAnnotation};
+use crate::span::Span;
 use crate::syntax_tree::Instruction;
-use crate::syntax_tree::Opcode;
+use crate::syntax_tree::Operation;
 use crate::syntax_tree::Parameter;
 use crate::syntax_tree::SyntaxTree;
 use crate::syntax_tree::Value;
 use crate::syntax_tree::ValueType;
+use crate::syntax_tree::WORD_DIRECTIVE;
 
-pub fn parse(text: &str) -> Result<Vec<u16>, String> {
-    let mut binary = Vec::<u16>::new();
-
-    let mut line_n = 1;
+/// Comments starting with this keyword are test annotations
+pub const TEST_ANNOTATION: &str = "@test";
 
+/// Assemble the whole text, every error is reported instead of stopping at the first one
+pub fn parse(text: &str) -> Result<Program, Vec<AssembleError>> {
     let mut syntax_tree = SyntaxTree::new();
+    let mut errors = Vec::new();
+    let mut tests = Vec::new();
 
-    for line in text.lines() {
-        if let Err(line_error) = parse_line(line, &mut syntax_tree) {
-            return Err(format!("Error line {line_n} : {line_error}"));
-        }
-
-        line_n += 1;
+    for (line_n, line) in (1..).zip(text.lines()) {
+        parse_line(line_n, line, &mut syntax_tree, &mut tests, &mut errors);
     }
 
-    match syntax_tree.generate_instructions() {
-        Ok(instructions) => {
-            for instruction in &instructions {
-                match generate_instruction(instruction) {
-                    Ok(bin) => {
-                        binary.push(bin);
-                    }
-                    Err(errmsg) => {
-                        return Err(errmsg);
-                    }
-                }
+    let mut binary = Vec::new();
+    let mut source_lines = Vec::new();
+    let (instructions, symbols) = syntax_tree.generate_instructions(&mut errors);
+
+    for instruction in instructions {
+        match generate_instruction(&instruction) {
+            Ok(bin) => {
+                binary.push(bin);
+                source_lines.push(SourceLine {
+                    line: instruction.span.line,
+                    text: instruction.source,
+                });
+            }
+            Err(error) => {
+                errors.push(error);
             }
         }
-        Err(err_msg) => {
-            return Err(err_msg);
-        }
     }
 
-    Ok(binary)
+    if errors.is_empty() {
+        Ok(Program::with_source(binary, source_lines, symbols, tests))
+    } else {
+        errors.sort_by_key(|error| (error.line, error.column));
+        Err(errors)
+    }
+}
+
+/// A word of the source and its position
+#[derive(Clone, Copy)]
+struct Word<'a> {
+    span: Span,
+    text: &'a str,
+    /// Byte index of the end of the word in the code
+    end: usize,
 }
 
-fn parse_line(line: &str, syntax_tree: &mut SyntaxTree) -> Result<(), String> {
-    let mut words = line.split(';').next().unwrap().split_whitespace();
-
-    if let Some(instruction_str) = words.next() {
-        if let Some(const_name) = instruction_str.strip_prefix('$') {
-            if let Some(value_str) = words.next() {
-                if let Ok(value) = value_str.parse::<u64>() {
-                    if value < 256 {
-                        syntax_tree.add_const(const_name, value as u8)?;
-                    } else {
-                        return Err("constant values can't be greater than 255".to_owned());
-                    }
-                } else {
-                    return Err("Can't parse constant value".to_owned());
-                }
-            } else {
-                return Err("You must specify a value after declaring a constant".to_owned());
-            }
-        } else if let Some(label_name) = instruction_str.strip_prefix(':') {
-            syntax_tree.add_label(label_name)?;
+impl Word<'_> {
+    fn error(self, kind: ErrorKind) -> AssembleError {
+        AssembleError::at(self.span, self.text, kind)
+    }
+}
+
+/// Calls `on_char` with every character of the line and whether it is inside a character literal
+fn scan_quotes(line: &str, mut on_char: impl FnMut(usize, char, bool) -> bool) {
+    let mut in_quote = false;
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        let quoted = in_quote || c == '\'';
+
+        if !on_char(index, c, quoted) {
+            return;
+        }
+
+        if in_quote && c == '\\' && !escaped {
+            escaped = true;
         } else {
-            match parse_instruction(instruction_str) {
-                Ok(instruction) => {
-                    let mut parameters = Vec::<Parameter>::new();
-                    for parameter_str in words {
-                        match parse_parameter(parameter_str) {
-                            Ok(parameter) => {
-                                parameters.push(parameter);
-                            }
-
-                            Err(error) => return Err(error),
-                        }
-                    }
-
-                    syntax_tree.add_instruction(Instruction::new(instruction, parameters))?;
-                }
-
-                Err(error) => return Err(error),
+            if c == '\'' && !escaped {
+                in_quote = !in_quote;
             }
+            escaped = false;
+        }
+    }
+}
+
+/// Split a line in its code and the comment that follows `;`, a `;` between quotes isn't a comment
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut comment_start = None;
+
+    scan_quotes(line, |index, c, quoted| {
+        if c == ';' && !quoted {
+            comment_start = Some(index);
         }
+        comment_start.is_none()
+    });
+
+    match comment_start {
+        Some(start) => (&line[..start], Some(&line[start + 1..])),
+        None => (line, None),
+    }
+}
+
+/// Test annotation in a comment, like `;@test name ram[0]=3 expect acc=3`
+fn parse_annotation(
+    line_n: usize,
+    line: &str,
+    comment: &str,
+) -> Option<Result<TestAnnotation, AssembleError>> {
+    let text = comment.strip_prefix(TEST_ANNOTATION)?;
+
+    // `;@testing` is a regular comment
+    if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let text = text.trim();
+
+    if text.is_empty() {
+        let start = line.len() - comment.len();
+        let span = Span::new(
+            line_n,
+            line[..start].chars().count() + 1,
+            TEST_ANNOTATION.len(),
+        );
+        return Some(Err(AssembleError::at(
+            span,
+            TEST_ANNOTATION,
+            ErrorKind::MissingTestName,
+        )));
     }
 
-    Ok(())
+    Some(Ok(TestAnnotation {
+        line: line_n,
+        text: text.to_owned(),
+    }))
 }
 
-fn parse_instruction(word: &str) -> Result<Opcode, String> {
-    match word {
-        "ST" => Ok(Opcode::St),
-        "LD" => Ok(Opcode::Ld),
-        "AND" => Ok(Opcode::And),
-        "OR" => Ok(Opcode::Or),
-        "XOR" => Ok(Opcode::Xor),
-        "ROL" => Ok(Opcode::Rol),
-        "ROR" => Ok(Opcode::Ror),
-        "ADD" => Ok(Opcode::Add),
-        "ADC" => Ok(Opcode::Adc),
-        "NEG" => Ok(Opcode::Neg),
-        "INC" => Ok(Opcode::Inc),
-        "DEC" => Ok(Opcode::Dec),
-        "SETC" => Ok(Opcode::Setc),
-        "CLRC" => Ok(Opcode::Clrc),
-        "TRFNC" => Ok(Opcode::Trfnc),
-        "BZ0" => Ok(Opcode::Bz0),
-        "BZ1" => Ok(Opcode::Bz1),
-        "BC0" => Ok(Opcode::Bc0),
-        "BC1" => Ok(Opcode::Bc1),
-        "BV0" => Ok(Opcode::Bv0),
-        "BV1" => Ok(Opcode::Bv1),
-        "BN0" => Ok(Opcode::Bn0),
-        "BN1" => Ok(Opcode::Bn1),
-        "BRA" => Ok(Opcode::Bra),
-        "NOP" => Ok(Opcode::Nop),
-        _ => Err("Unknown instruction name".to_owned()),
+/// First word of the code part of a line
+fn first_word(line_n: usize, code: &str) -> Option<Word<'_>> {
+    let start = code.find(|c: char| !c.is_whitespace())?;
+    let end = code[start..]
+        .find(char::is_whitespace)
+        .map_or(code.len(), |length| start + length);
+    let text = &code[start..end];
+
+    Some(Word {
+        span: Span::new(
+            line_n,
+            code[..start].chars().count() + 1,
+            text.chars().count(),
+        ),
+        text,
+        end,
+    })
+}
+
+/// Byte indexes of the text following `word`, without the surrounding whitespace
+fn rest_of_line(code: &str, word: Word) -> (usize, usize) {
+    let rest = &code[word.end..];
+    let start = word.end + (rest.len() - rest.trim_start().len());
+    let end = word.end + rest.trim_end().len();
+
+    (start, end.max(start))
+}
+
+fn check_name(name: &str) -> Result<(), ErrorKind> {
+    if !name.is_empty() && name.chars().all(is_name_char) {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidName)
     }
 }
 
-fn parse_parameter(word: &str) -> Result<Parameter, String> {
-    if word == "ACC" {
-        Ok(Parameter::Acc)
+/// Parse a line into the syntax tree, a line with errors adds nothing to the tree
+///
+/// A comment holding a test annotation is kept in `tests`, other comments are dropped
+fn parse_line(
+    line_n: usize,
+    line: &str,
+    syntax_tree: &mut SyntaxTree,
+    tests: &mut Vec<TestAnnotation>,
+    errors: &mut Vec<AssembleError>,
+) {
+    let (code, comment) = split_comment(line);
+
+    match comment.and_then(|comment| parse_annotation(line_n, line, comment)) {
+        Some(Ok(test)) => tests.push(test),
+        Some(Err(annotation_error)) => errors.push(annotation_error),
+        None => {}
+    }
+
+    let Some(first_word) = first_word(line_n, code) else {
+        return;
+    };
+
+    let (rest_start, rest_end) = rest_of_line(code, first_word);
+
+    let result = if let Some(const_name) = first_word.text.strip_prefix('$') {
+        if rest_start == rest_end {
+            Err(first_word.error(ErrorKind::MissingConstantValue))
+        } else {
+            check_name(const_name)
+                .map_err(|kind| first_word.error(kind))
+                .and_then(|()| parse_expression(line_n, code, rest_start, rest_end))
+                .and_then(|expr| {
+                    syntax_tree
+                        .add_const(const_name, expr, first_word.span)
+                        .map_err(|kind| first_word.error(kind))
+                })
+        }
+    } else if let Some(label_name) = first_word.text.strip_prefix(':') {
+        check_name(label_name)
+            .and_then(|()| syntax_tree.add_label(label_name, first_word.span))
+            .map_err(|kind| first_word.error(kind))
     } else {
-        let mut parameter_str = word.to_string();
-        let mut direct = true;
-
-        if let Some(first_strip) = word.strip_prefix('[') {
-            if let Some(second_strip) = first_strip.strip_suffix(']') {
-                parameter_str = second_strip.to_owned();
-                direct = false;
-            } else {
-                return Err("Can't parse parameter, did you forget a closing ] ?".to_owned());
+        let operation = parse_operation(first_word.text).map_err(|kind| first_word.error(kind));
+        let parameter = parse_parameter(line_n, code, rest_start, rest_end);
+
+        match (operation, parameter) {
+            (Ok(operation), Ok(parameter)) => syntax_tree
+                .add_instruction(Instruction::new(
+                    operation,
+                    parameter,
+                    first_word.span,
+                    line.to_owned(),
+                ))
+                .map_err(|kind| first_word.error(kind)),
+            (Err(operation_error), Err(parameter_error)) => {
+                errors.push(operation_error);
+                Err(parameter_error)
             }
+            (Err(error), _) | (_, Err(error)) => Err(error),
         }
+    };
 
-        if let Ok(n) = parameter_str.parse::<u64>() {
-            if n < 256 {
-                Ok(Parameter::Value(Value::new(
-                    direct,
-                    ValueType::Raw(n as u8),
-                )))
-            } else {
-                Err("Values can't be over 255".to_owned())
-            }
-        } else if let Some(const_name) = parameter_str.strip_prefix('$') {
-            Ok(Parameter::Value(Value::new(
-                direct,
-                ValueType::Const(const_name.to_owned()),
-            )))
-        } else if let Some(label_name) = parameter_str.strip_prefix(':') {
-            Ok(Parameter::Value(Value::new(
-                direct,
-                ValueType::Label(label_name.to_owned()),
-            )))
+    if let Err(line_error) = result {
+        errors.push(line_error);
+    }
+}
+
+fn parse_operation(word: &str) -> Result<Operation, ErrorKind> {
+    if word == WORD_DIRECTIVE {
+        Ok(Operation::Word)
+    } else {
+        Mnemonic::from_name(word)
+            .map(Operation::Instruction)
+            .ok_or(ErrorKind::UnknownInstruction)
+    }
+}
+
+/// Parse the operand found between the byte indexes `start` and `end` of the code
+fn parse_parameter(
+    line_n: usize,
+    code: &str,
+    start: usize,
+    end: usize,
+) -> Result<Option<Parameter>, AssembleError> {
+    let text = &code[start..end];
+
+    if text.is_empty() {
+        return Ok(None);
+    }
+
+    if text == "ACC" {
+        return Ok(Some(Parameter::Acc));
+    }
+
+    let (direct, expr_start, expr_end) = if text.starts_with('[') {
+        if text.len() > 1 && text.ends_with(']') {
+            (false, start + 1, end - 1)
         } else {
-            Err("Can't parse parameter".to_owned())
+            let span = Span::new(
+                line_n,
+                code[..start].chars().count() + 1,
+                text.chars().count(),
+            );
+            return Err(AssembleError::at(
+                span,
+                text,
+                ErrorKind::MissingClosingBracket,
+            ));
         }
-    }
+    } else {
+        (true, start, end)
+    };
+
+    let expr = parse_expression(line_n, code, expr_start, expr_end)?;
+
+    Ok(Some(Parameter::Value(Value::new(
+        direct,
+        ValueType::Expr(expr),
+    ))))
 }
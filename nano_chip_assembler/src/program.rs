@@ -0,0 +1,119 @@
+use std::fmt::Write;
+
+/// An assembled program, one 14 bits word per instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    words: Vec<u16>,
+    source_lines: Vec<SourceLine>,
+    symbols: Vec<Symbol>,
+    tests: Vec<TestAnnotation>,
+}
+
+/// Source line an instruction was assembled from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Text of the line, with its comment
+    pub text: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Constant,
+    Label,
+}
+
+/// A constant or label and its value, labels hold the ROM address of the next instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// Name without the `$` or `:` prefix
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: u8,
+    /// Line of the definition, starting at 1
+    pub line: usize,
+}
+
+/// Test case written in a `;@test` comment, its text is run by `nano_chip_test`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestAnnotation {
+    /// Line of the comment, starting at 1
+    pub line: usize,
+    /// Text following `@test`, starting with the test name
+    pub text: String,
+}
+
+impl Symbol {
+    /// Name as written in the source, with its prefix
+    pub fn prefixed_name(&self) -> String {
+        match self.kind {
+            SymbolKind::Constant => format!("${}", self.name),
+            SymbolKind::Label => format!(":{}", self.name),
+        }
+    }
+}
+
+impl Program {
+    /// Program without source information
+    pub const fn new(words: Vec<u16>) -> Self {
+        Self {
+            words,
+            source_lines: Vec::new(),
+            symbols: Vec::new(),
+            tests: Vec::new(),
+        }
+    }
+
+    pub(crate) const fn with_source(
+        words: Vec<u16>,
+        source_lines: Vec<SourceLine>,
+        symbols: Vec<Symbol>,
+        tests: Vec<TestAnnotation>,
+    ) -> Self {
+        Self {
+            words,
+            source_lines,
+            symbols,
+            tests,
+        }
+    }
+
+    pub fn words(&self) -> &[u16] {
+        &self.words
+    }
+
+    /// Source line of each word, empty if the program wasn't assembled from source
+    pub fn source_lines(&self) -> &[SourceLine] {
+        &self.source_lines
+    }
+
+    /// Constants sorted by name, then labels sorted by address
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// `;@test` annotations in source order
+    pub fn tests(&self) -> &[TestAnnotation] {
+        &self.tests
+    }
+
+    /// Test spec running every `;@test` annotation on `program_path`, the file `nano_chip_test` reads
+    pub fn test_manifest(&self, program_path: &str) -> String {
+        let mut manifest = format!("program {program_path}\n");
+
+        for test in &self.tests {
+            let _ = writeln!(manifest, "test {} ; line {}", test.text, test.line);
+        }
+
+        manifest
+    }
+
+    /// Binary file content, each word is stored big endian on two bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+}
@@ -0,0 +1,20 @@
+/// Position of a token in the source
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// Line number, starting at 1
+    pub line: usize,
+    /// Column of the first character, starting at 1
+    pub column: usize,
+    /// Length in characters
+    pub length: usize,
+}
+
+impl Span {
+    pub const fn new(line: usize, column: usize, length: usize) -> Self {
+        Self {
+            line,
+            column,
+            length,
+        }
+    }
+}
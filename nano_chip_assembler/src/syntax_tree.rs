@@ -1,41 +1,19 @@
 use std::collections::HashMap;
 
-const MAX_INSTRUCTIONS: usize = 128;
+use nano_chip_isa::{Mnemonic, ROM_SIZE};
 
-#[derive(Clone)]
-pub enum Opcode {
-    St,
-    Ld,
-    And,
-    Or,
-    Xor,
-    Rol,
-    Ror,
-    Add,
-    Adc,
-    Neg,
-    Inc,
-    Dec,
-    Setc,
-    Clrc,
-    Trfnc,
-    Bz0,
-    Bz1,
-    Bc0,
-    Bc1,
-    Bv0,
-    Bv1,
-    Bn0,
-    Bn1,
-    Bra,
-    Nop,
-}
+use crate::error::{AssembleError, ErrorKind};
+use crate::expression::{Expr, ExprKind};
+use crate::literal::to_byte;
+use crate::program::{Symbol, SymbolKind};
+use crate::span::Span;
+
+pub const MAX_INSTRUCTIONS: usize = ROM_SIZE;
 
 #[derive(Clone)]
 pub enum ValueType {
-    Raw(u8),
-    Const(String),
-    Label(String),
+    Raw(u16),
+    Expr(Expr),
 }
 
 #[derive(Clone)]
@@ -56,22 +34,74 @@ pub enum Parameter {
     Value(Value),
 }
 
+/// Directive that stores its value as is in the ROM
+pub const WORD_DIRECTIVE: &str = ".word";
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Instruction(Mnemonic),
+    /// Data word written with the `.word` directive
+    Word,
+}
+
+impl Operation {
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Instruction(mnemonic) => mnemonic.name(),
+            Self::Word => WORD_DIRECTIVE,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Instruction {
-    pub opcode: Opcode,
-    pub param: Vec<Parameter>,
+    pub operation: Operation,
+    pub param: Option<Parameter>,
+    /// Position of the mnemonic or directive in the source
+    pub span: Span,
+    /// Whole source line, with its comment
+    pub source: String,
 }
 
 impl Instruction {
-    pub fn new(opcode: Opcode, param: Vec<Parameter>) -> Self {
-        Self { opcode, param }
+    pub const fn new(
+        operation: Operation,
+        param: Option<Parameter>,
+        span: Span,
+        source: String,
+    ) -> Self {
+        Self {
+            operation,
+            param,
+            span,
+            source,
+        }
     }
 }
 
+/// Value of a label and where it was defined
+#[derive(Clone, Copy)]
+pub struct Label {
+    pub value: u8,
+    pub span: Span,
+}
+
+/// A constant as written in the source, its value is computed once every label is known
+pub struct Constant {
+    pub expr: Expr,
+    pub span: Span,
+}
+
+/// State of a constant while the constants are evaluated
+enum Evaluation {
+    InProgress,
+    Done(Option<i64>),
+}
+
 pub struct SyntaxTree {
     instructions: Vec<Instruction>,
-    constants: HashMap<String, u8>,
-    labels: HashMap<String, u8>,
+    constants: HashMap<String, Constant>,
+    labels: HashMap<String, Label>,
 }
 
 impl SyntaxTree {
@@ -83,103 +113,214 @@ impl SyntaxTree {
         }
     }
 
-    pub fn add_instruction(&mut self, instruction: Instruction) -> Result<(), String> {
-        for parameter in &instruction.param {
-            if let Parameter::Value(value) = parameter {
-                if let ValueType::Label(_) = value.value_type {
-                    if !value.direct {
-                        return Err("Indirect addressing on labels is not allowed".to_owned());
-                    }
+    pub fn add_instruction(&mut self, instruction: Instruction) -> Result<(), ErrorKind> {
+        let direct_value =
+            matches!(&instruction.param, Some(Parameter::Value(value)) if value.direct);
+
+        if matches!(instruction.operation, Operation::Word) && !direct_value {
+            return Err(ErrorKind::InvalidWord);
+        }
+
+        if let Some(Parameter::Value(value)) = &instruction.param {
+            if let ValueType::Expr(expr) = &value.value_type {
+                if !value.direct && expr.references_label() {
+                    return Err(ErrorKind::IndirectLabel);
                 }
             }
         }
 
         self.instructions.push(instruction);
 
-        if self.instructions.len() > MAX_INSTRUCTIONS {
-            Err(format!("Too many instructions, a program can contain at most {MAX_INSTRUCTIONS} instructions"))
+        // Only the first instruction over the limit is reported
+        if self.instructions.len() == MAX_INSTRUCTIONS + 1 {
+            Err(ErrorKind::TooManyInstructions)
         } else {
             Ok(())
         }
     }
 
-    pub fn add_const(&mut self, const_name: &str, global_value: u8) -> Result<(), String> {
-        if self
-            .constants
-            .insert(const_name.to_owned(), global_value)
-            .is_some()
-        {
-            Err(format!("A global named {const_name} already exists"))
+    pub fn add_const(&mut self, const_name: &str, expr: Expr, span: Span) -> Result<(), ErrorKind> {
+        if let Some(previous) = self.constants.get(const_name) {
+            Err(ErrorKind::DuplicateConstant(previous.span))
         } else {
+            self.constants
+                .insert(const_name.to_owned(), Constant { expr, span });
             Ok(())
         }
     }
 
-    pub fn add_label(&mut self, label_name: &str) -> Result<(), String> {
-        if self
-            .labels
-            .insert(label_name.to_owned(), self.instructions.len() as u8)
-            .is_some()
-        {
-            Err(format!("A label named {label_name} already exists"))
+    pub fn add_label(&mut self, label_name: &str, span: Span) -> Result<(), ErrorKind> {
+        if let Some(previous) = self.labels.get(label_name) {
+            Err(ErrorKind::DuplicateLabel(previous.span))
         } else {
+            // A full ROM has no address left for a label after its last instruction
+            let value = u8::try_from(self.instructions.len())
+                .ok()
+                .filter(|_| self.instructions.len() < MAX_INSTRUCTIONS)
+                .ok_or(ErrorKind::LabelPastEnd)?;
+            self.labels
+                .insert(label_name.to_owned(), Label { value, span });
             Ok(())
         }
     }
 
-    // Replace constant and label name by their raw value
-    fn process_instruction(&self, instruction: &Instruction) -> Result<Instruction, String> {
-        let mut new_parameters = Vec::new();
-
-        for parameter in &instruction.param {
-            new_parameters.push(if let Parameter::Value(value) = parameter {
-                match &value.value_type {
-                    ValueType::Const(const_name) => {
-                        if let Some(const_value) = self.constants.get(const_name) {
-                            Parameter::Value(Value::new(
-                                value.direct,
-                                ValueType::Raw(const_value.to_owned()),
-                            ))
-                        } else {
-                            return Err(format!("Constant named {const_name} doesn't exist"));
-                        }
-                    }
-                    ValueType::Label(label_name) => {
-                        if let Some(label_value) = self.labels.get(label_name) {
-                            Parameter::Value(Value::new(
-                                value.direct,
-                                ValueType::Raw(label_value.to_owned()),
-                            ))
-                        } else {
-                            return Err(format!("Label named {label_name} doesn't exist"));
-                        }
-                    }
-                    _ => parameter.clone(),
+    /// Value of a constant, each constant is evaluated once so that its errors are reported once
+    fn constant_value(
+        &self,
+        const_name: &str,
+        evaluations: &mut HashMap<String, Evaluation>,
+        errors: &mut Vec<AssembleError>,
+    ) -> Option<i64> {
+        if let Some(Evaluation::Done(value)) = evaluations.get(const_name) {
+            return *value;
+        }
+
+        let constant = &self.constants[const_name];
+
+        evaluations.insert(const_name.to_owned(), Evaluation::InProgress);
+
+        let value = constant
+            .expr
+            .evaluate(
+                &mut |expr, errors| self.symbol_value(expr, evaluations, errors),
+                errors,
+            )
+            .filter(|&value| {
+                let in_range = to_byte(value).is_some();
+
+                if !in_range {
+                    errors.push(AssembleError::at(
+                        constant.expr.span,
+                        &constant.expr.text,
+                        ErrorKind::ConstantOutOfRange,
+                    ));
                 }
-            } else {
-                parameter.clone()
+
+                in_range
             });
-        }
 
-        Ok(Instruction::new(instruction.opcode.clone(), new_parameters))
+        evaluations.insert(const_name.to_owned(), Evaluation::Done(value));
+
+        value
     }
 
-    /// Generate the list of instructions, ready to be converted to machine code
-    /// Replaces constants and labels with their raw value
-    pub fn generate_instructions(&self) -> Result<Vec<Instruction>, String> {
-        let mut checked_instructions = Vec::new();
+    /// Value of a constant or label used in an expression
+    fn symbol_value(
+        &self,
+        expr: &Expr,
+        evaluations: &mut HashMap<String, Evaluation>,
+        errors: &mut Vec<AssembleError>,
+    ) -> Option<i64> {
+        let error = |kind| AssembleError::at(expr.span, &expr.text, kind);
 
-        for instruction in &self.instructions {
-            match self.process_instruction(instruction) {
-                Ok(instr) => {
-                    checked_instructions.push(instr);
+        match &expr.kind {
+            ExprKind::Const(const_name) => {
+                if !self.constants.contains_key(const_name) {
+                    errors.push(error(ErrorKind::UnknownConstant));
+                    None
+                } else if matches!(evaluations.get(const_name), Some(Evaluation::InProgress)) {
+                    errors.push(error(ErrorKind::CircularConstant));
+                    None
+                } else {
+                    self.constant_value(const_name, evaluations, errors)
                 }
-                Err(errmsg) => {
-                    return Err(errmsg);
+            }
+            ExprKind::Label(label_name) => {
+                if let Some(label) = self.labels.get(label_name) {
+                    Some(i64::from(label.value))
+                } else {
+                    errors.push(error(ErrorKind::UnknownLabel));
+                    None
                 }
             }
+            _ => unreachable!(),
         }
+    }
+
+    // Replace the operand expression by its raw value, a byte for instructions and 16 bits for data words
+    fn process_instruction(
+        &self,
+        instruction: &Instruction,
+        evaluations: &mut HashMap<String, Evaluation>,
+        errors: &mut Vec<AssembleError>,
+    ) -> Option<Instruction> {
+        let mut new_instruction = instruction.clone();
+
+        if let Some(Parameter::Value(value)) = &mut new_instruction.param {
+            if let ValueType::Expr(expr) = &value.value_type {
+                let result = expr.evaluate(
+                    &mut |expr, errors| self.symbol_value(expr, evaluations, errors),
+                    errors,
+                )?;
+
+                let raw = match instruction.operation {
+                    Operation::Instruction(_) => to_byte(result)
+                        .map(u16::from)
+                        .ok_or(ErrorKind::ValueOutOfRange),
+                    Operation::Word => u16::try_from(result).map_err(|_| ErrorKind::InvalidWord),
+                };
+
+                match raw {
+                    Ok(raw) => value.value_type = ValueType::Raw(raw),
+                    Err(kind) => {
+                        errors.push(AssembleError::at(expr.span, &expr.text, kind));
+                        return None;
+                    }
+                }
+            }
+        }
+
+        Some(new_instruction)
+    }
+
+    /// Generate the list of instructions, ready to be converted to machine code, and the symbol table
+    /// Replaces constants and labels with their raw value
+    ///
+    /// Instructions that can't be resolved are left out and their errors are added to `errors`
+    pub fn generate_instructions(
+        &self,
+        errors: &mut Vec<AssembleError>,
+    ) -> (Vec<Instruction>, Vec<Symbol>) {
+        let mut evaluations = HashMap::new();
+
+        // Every constant is evaluated, even unused ones, so that their errors are reported
+        let mut const_names = self.constants.keys().collect::<Vec<_>>();
+        const_names.sort();
+
+        let mut symbols = Vec::new();
+
+        for const_name in const_names {
+            if let Some(value) = self
+                .constant_value(const_name, &mut evaluations, errors)
+                .and_then(to_byte)
+            {
+                symbols.push(Symbol {
+                    name: const_name.clone(),
+                    kind: SymbolKind::Constant,
+                    value,
+                    line: self.constants[const_name].span.line,
+                });
+            }
+        }
+
+        let mut labels = self.labels.iter().collect::<Vec<_>>();
+        labels.sort_by_key(|(label_name, label)| (label.value, *label_name));
+
+        symbols.extend(labels.into_iter().map(|(label_name, label)| Symbol {
+            name: label_name.clone(),
+            kind: SymbolKind::Label,
+            value: label.value,
+            line: label.span.line,
+        }));
+
+        let instructions = self
+            .instructions
+            .iter()
+            .filter_map(|instruction| {
+                self.process_instruction(instruction, &mut evaluations, errors)
+            })
+            .collect();
 
-        Ok(checked_instructions)
+        (instructions, symbols)
     }
 }
@@ -1,6 +1,13 @@
 #![cfg(test)]
 
-use crate::parser::parse;
+use crate::{
+    assemble, disassemble, AssembleError, DebugInfo, DebugInfoError, DisassembleError, ErrorKind,
+    SourceLine, Span, Symbol, SymbolKind, TestAnnotation,
+};
+
+fn parse(text: &str) -> Result<Vec<u16>, Vec<AssembleError>> {
+    assemble(text).map(|program| program.words().to_vec())
+}
 
 #[test]
 fn test_st() {
@@ -83,6 +90,7 @@ fn test_clrc() {
 }
 
 #[test]
+#[allow(clippy::nonstandard_macro_braces)]
 fn test_trfnc() {
     assert_eq!(parse("TRFNC"), Ok(vec!(0x1900)));
 }
@@ -150,3 +158,463 @@ fn test_comment() {
         Ok(vec![0x3F00])
     );
 }
+
+#[test]
+fn test_error_location() {
+    assert_eq!(
+        parse("NOP\n  LD [42"),
+        Err(vec![AssembleError::new(
+            2,
+            6,
+            "[42",
+            ErrorKind::MissingClosingBracket
+        )])
+    );
+    assert_eq!(
+        parse("FOO 42"),
+        Err(vec![AssembleError::new(
+            1,
+            1,
+            "FOO",
+            ErrorKind::UnknownInstruction
+        )])
+    );
+    assert_eq!(
+        parse("$a 300"),
+        Err(vec![AssembleError::new(
+            1,
+            4,
+            "300",
+            ErrorKind::ConstantOutOfRange
+        )])
+    );
+}
+
+#[test]
+fn test_resolution_errors() {
+    assert_eq!(
+        parse("NOP\nBRA :nowhere"),
+        Err(vec![AssembleError::new(
+            2,
+            5,
+            ":nowhere",
+            ErrorKind::UnknownLabel
+        )])
+    );
+    assert_eq!(
+        parse("\tST 42"),
+        Err(vec![AssembleError::new(
+            1,
+            2,
+            "ST",
+            ErrorKind::WrongAddressingMode(nano_chip_isa::Mnemonic::St)
+        )])
+    );
+}
+
+#[test]
+fn test_every_error_is_reported() {
+    let errors = parse(
+        "
+        $a 1
+        $a 2
+        FOO
+        LD [$a
+        LD $b
+        :loop
+        ST $a
+        BRA :loop
+        BRA :nowhere
+        ",
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| (error.line, error.kind.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (3, ErrorKind::DuplicateConstant(Span::new(2, 9, 2))),
+            (4, ErrorKind::UnknownInstruction),
+            (5, ErrorKind::MissingClosingBracket),
+            (6, ErrorKind::UnknownConstant),
+            (
+                8,
+                ErrorKind::WrongAddressingMode(nano_chip_isa::Mnemonic::St)
+            ),
+            (10, ErrorKind::UnknownLabel),
+        ]
+    );
+}
+
+#[test]
+fn test_too_many_instructions_reported_once() {
+    let errors = parse(&"NOP\n".repeat(300)).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::TooManyInstructions);
+    assert_eq!(errors[0].line, 257);
+}
+
+#[test]
+fn test_full_rom() {
+    let words = parse(&format!("{}:last\nBRA :last", "NOP\n".repeat(255))).unwrap();
+    assert_eq!(words.len(), 256);
+    assert_eq!(words[255], 0x22FF);
+}
+
+#[test]
+fn test_label_past_end() {
+    let errors = parse(&format!("{}:end\nBRA :end", "NOP\n".repeat(256))).unwrap_err();
+    assert_eq!(errors[0].kind, ErrorKind::LabelPastEnd);
+    assert_eq!(errors[0].line, 257);
+    assert_eq!(
+        errors[0].kind.to_string(),
+        "Label past the end of the ROM, it must be preceded by at most 255 instructions"
+    );
+
+    // Also reported when nothing follows the label
+    let errors = parse(&format!("{}:end", "NOP\n".repeat(256))).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::LabelPastEnd);
+}
+
+#[test]
+fn test_render() {
+    let source = "NOP\n\tLD [$missing] ; comment";
+    let errors = assemble(source).unwrap_err();
+
+    assert_eq!(
+        errors[0].render("test.asm", source, false),
+        "error: Constant doesn't exist
+ --> test.asm:2:6
+  |
+2 | \tLD [$missing] ; comment
+  | \t    ^^^^^^^^
+"
+    );
+
+    // Errors built without a position don't point into the source
+    let error = AssembleError::new(0, 0, "", ErrorKind::UnknownLabel);
+    assert_eq!(
+        error.render("test.asm", source, false),
+        "error: Label doesn't exist\n --> test.asm:0:0\n  |\n0 | \n  | ^\n"
+    );
+}
+
+#[test]
+fn test_literals() {
+    assert_eq!(parse("LD 0x2A"), Ok(vec![0x022A]));
+    assert_eq!(parse("XOR 0xFF"), Ok(vec![0x08FF]));
+    assert_eq!(parse("AND 0b0000_1111"), Ok(vec![0x040F]));
+    assert_eq!(parse("LD [1_0]"), Ok(vec![0x030A]));
+    assert_eq!(parse("LD 'A'"), Ok(vec![0x0241]));
+    assert_eq!(parse("LD ' ' ; space"), Ok(vec![0x0220]));
+    assert_eq!(parse("LD ';'"), Ok(vec![0x023B]));
+    assert_eq!(parse("LD '\\''"), Ok(vec![0x0227]));
+    assert_eq!(parse("LD '\\n'"), Ok(vec![0x020A]));
+    assert_eq!(parse("LD -5"), Ok(vec![0x02FB]));
+    assert_eq!(parse("LD -128"), Ok(vec![0x0280]));
+    assert_eq!(parse("$mask 0b1010\nAND $mask"), Ok(vec![0x040A]));
+    assert_eq!(parse("$minus -1\nADD $minus"), Ok(vec![0x0CFF]));
+}
+
+#[test]
+fn test_literal_errors() {
+    let kind = |source| parse(source).unwrap_err()[0].kind.clone();
+
+    assert_eq!(kind("LD 256"), ErrorKind::ValueOutOfRange);
+    assert_eq!(kind("LD -129"), ErrorKind::ValueOutOfRange);
+    assert_eq!(kind("LD 0x100"), ErrorKind::ValueOutOfRange);
+    assert_eq!(kind("LD 99999999999999999999"), ErrorKind::ValueOutOfRange);
+    assert_eq!(kind("LD 0xZZ"), ErrorKind::InvalidNumber);
+    assert_eq!(kind("LD 0b102"), ErrorKind::InvalidNumber);
+    assert_eq!(kind("LD 0x"), ErrorKind::InvalidNumber);
+    assert_eq!(kind("LD 'AB'"), ErrorKind::InvalidCharacter);
+    assert_eq!(kind("LD 'é'"), ErrorKind::InvalidCharacter);
+    assert_eq!(kind("$big 0x1FF"), ErrorKind::ConstantOutOfRange);
+    assert_eq!(kind("$bad 12a"), ErrorKind::InvalidNumber);
+}
+
+#[test]
+fn test_expressions() {
+    assert_eq!(parse("$buf 16\nLD [$buf+3]"), Ok(vec![0x0313]));
+    assert_eq!(
+        parse("$start 0x20\n$end $start+16\nLD $end"),
+        Ok(vec![0x0230])
+    );
+    assert_eq!(parse("$mask 0x0F\nAND ~$mask"), Ok(vec![0x04F0]));
+    assert_eq!(
+        parse(":table\nNOP\nNOP\n:table_end\nLD :table_end-:table"),
+        Ok(vec![0x3F00, 0x3F00, 0x0202])
+    );
+    assert_eq!(parse("LD 2 + 3 * 4"), Ok(vec![0x020E]));
+    assert_eq!(parse("LD (2 + 3) * 4"), Ok(vec![0x0214]));
+    assert_eq!(parse("LD 1 << 4 | 1"), Ok(vec![0x0211]));
+    assert_eq!(parse("LD 0xF0 >> 4 ^ 0b11"), Ok(vec![0x020C]));
+    assert_eq!(parse("LD 17 / 5 + 17 % 5"), Ok(vec![0x0205]));
+    assert_eq!(parse("LD -(1 + 1)"), Ok(vec![0x02FE]));
+    assert_eq!(parse("LD 'a' - 'A'"), Ok(vec![0x0220]));
+    assert_eq!(
+        parse("LD $after\n$after $before * 2\n$before 3"),
+        Ok(vec![0x0206])
+    );
+    assert_eq!(
+        parse("BRA :end + 1 ; skip\n:end\nNOP"),
+        Ok(vec![0x2202, 0x3F00])
+    );
+}
+
+#[test]
+fn test_expression_errors() {
+    let error = |source| {
+        let error = parse(source).unwrap_err()[0].clone();
+        (error.column, error.token, error.kind)
+    };
+
+    assert_eq!(
+        error("LD 4 / (2 - 2)"),
+        (8, "(2 - 2)".to_owned(), ErrorKind::DivisionByZero)
+    );
+    assert_eq!(
+        error("LD 1 << 64"),
+        (9, "64".to_owned(), ErrorKind::InvalidShift)
+    );
+    assert_eq!(
+        error("LD 200 + 100"),
+        (4, "200 + 100".to_owned(), ErrorKind::ValueOutOfRange)
+    );
+    assert_eq!(
+        error("$a 200\n$b $a * 2"),
+        (4, "$a * 2".to_owned(), ErrorKind::ConstantOutOfRange)
+    );
+    assert_eq!(
+        error("LD (1 + 2"),
+        (10, String::new(), ErrorKind::MissingClosingParenthesis)
+    );
+    assert_eq!(
+        error("LD 1 +"),
+        (7, String::new(), ErrorKind::ExpectedValue)
+    );
+    assert_eq!(
+        error("LD 1 2"),
+        (6, "2".to_owned(), ErrorKind::UnexpectedToken)
+    );
+    assert_eq!(
+        error("LD 1 # 2"),
+        (6, "#".to_owned(), ErrorKind::UnexpectedToken)
+    );
+    assert_eq!(error("LD $"), (4, "$".to_owned(), ErrorKind::InvalidName));
+    assert_eq!(
+        error("$a-b 1"),
+        (1, "$a-b".to_owned(), ErrorKind::InvalidName)
+    );
+    assert_eq!(
+        error(":a\nLD [:a + 1]"),
+        (1, "LD".to_owned(), ErrorKind::IndirectLabel)
+    );
+
+    let errors = parse("$a $b + 1\n$b $a\nLD $a").unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| (error.line, error.kind.clone()))
+            .collect::<Vec<_>>(),
+        vec![(2, ErrorKind::CircularConstant)]
+    );
+}
+
+#[test]
+fn test_source_lines_and_symbols() {
+    let program = assemble("$b 2\n$a $b + 1\n:start\n  LD [$a] ; load\nBRA :start").unwrap();
+
+    assert_eq!(
+        program.source_lines(),
+        [
+            SourceLine {
+                line: 4,
+                text: "  LD [$a] ; load".to_owned()
+            },
+            SourceLine {
+                line: 5,
+                text: "BRA :start".to_owned()
+            }
+        ]
+    );
+
+    let symbol = |name: &str, kind, value, line| Symbol {
+        name: name.to_owned(),
+        kind,
+        value,
+        line,
+    };
+
+    assert_eq!(
+        program.symbols(),
+        [
+            symbol("a", SymbolKind::Constant, 3, 2),
+            symbol("b", SymbolKind::Constant, 2, 1),
+            symbol("start", SymbolKind::Label, 0, 3),
+        ]
+    );
+}
+
+#[test]
+fn test_listing() {
+    let program = assemble("$a 3\n:start\n  LD [$a] ; load\nBRA :start").unwrap();
+
+    assert_eq!(
+        program.listing(),
+        "Addr  Hex   Binary           Line  Source
+0x00  0303  000011 00000011     3  LD [$a] ; load
+0x01  2200  100010 00000000     4  BRA :start
+
+Symbol  Kind      Value     Line
+$a      constant  0x03   3     1
+:start  label     0x00   0     2
+"
+    );
+}
+
+#[test]
+fn test_debug_info() {
+    let program = assemble("$a 3\n:start\n  LD [$a] ; load\nBRA :start").unwrap();
+    let debug_info = program.debug_info("/code/my program.asm");
+
+    assert_eq!(
+        debug_info.to_string(),
+        "nano_chip_debug 1
+source /code/my program.asm
+rom 2 AD555B71C03A6059
+constant a 0x03 1
+label start 0x00 2
+line 0x00 3
+line 0x01 4
+"
+    );
+    assert_eq!(DebugInfo::parse(&debug_info.to_string()), Ok(debug_info));
+
+    assert_eq!(
+        DebugInfo::parse("nano_chip_debug 2\nsource a.asm"),
+        Err(DebugInfoError {
+            line: 1,
+            message: "expected `nano_chip_debug 1`".to_owned()
+        })
+    );
+    assert_eq!(
+        DebugInfo::parse("nano_chip_debug 1\nsource a.asm\nrom 1 0\nline 0x01 3"),
+        Err(DebugInfoError {
+            line: 4,
+            message: "expected address 0x00, got `0x01`".to_owned()
+        })
+    );
+    assert_eq!(
+        DebugInfo::parse("nano_chip_debug 1\nlabel start 0x00 2"),
+        Err(DebugInfoError {
+            line: 2,
+            message: "missing source".to_owned()
+        })
+    );
+    assert_eq!(
+        DebugInfo::parse("nano_chip_debug 1\nsource a.asm"),
+        Err(DebugInfoError {
+            line: 2,
+            message: "missing rom".to_owned()
+        })
+    );
+}
+
+#[test]
+fn test_test_annotations() {
+    let program = assemble(
+        ";@test first ram[0]=3 expect acc=3\nLD [0] ;@test  second  expect z=0 \n; @test not_a_test\n;@testing",
+    )
+    .unwrap();
+
+    assert_eq!(
+        program.tests(),
+        [
+            TestAnnotation {
+                line: 1,
+                text: "first ram[0]=3 expect acc=3".to_owned()
+            },
+            TestAnnotation {
+                line: 2,
+                text: "second  expect z=0".to_owned()
+            },
+        ]
+    );
+    assert_eq!(
+        program.test_manifest("/code/program.asm"),
+        "program /code/program.asm
+test first ram[0]=3 expect acc=3 ; line 1
+test second  expect z=0 ; line 2
+"
+    );
+
+    // Annotation errors are reported along with the errors of the code
+    assert_eq!(
+        assemble("NOP ;@test\nFOO ;@test name").unwrap_err(),
+        [
+            AssembleError::new(1, 6, "@test", ErrorKind::MissingTestName),
+            AssembleError::new(2, 1, "FOO", ErrorKind::UnknownInstruction),
+        ]
+    );
+}
+
+#[test]
+fn test_data_words() {
+    assert_eq!(
+        parse(".word 0x1234\n.word :end\n:end"),
+        Ok(vec![0x1234, 0x0002])
+    );
+
+    let kind = |source| parse(source).unwrap_err()[0].kind.clone();
+
+    assert_eq!(kind(".word 0x10000"), ErrorKind::InvalidWord);
+    assert_eq!(kind(".word -1"), ErrorKind::InvalidWord);
+    assert_eq!(kind(".word [3]"), ErrorKind::InvalidWord);
+    assert_eq!(kind(".word"), ErrorKind::InvalidWord);
+}
+
+#[test]
+fn test_disassemble() {
+    let fibonacci = include_bytes!("../../examples/fibonacci.o");
+    let source = disassemble(fibonacci).unwrap();
+
+    assert!(source.starts_with("LD 0x01\nST [0x00]\nST [0x01]\n:L03\nLD [0x00]\n"));
+    assert!(source.ends_with("BRA :L03\n"));
+    assert_eq!(assemble(&source).unwrap().to_bytes(), fibonacci);
+
+    assert_eq!(
+        disassemble(&[0x23, 0x00, 0x0A, 0x01, 0x22, 0x03]).unwrap(),
+        ".word 0x2300 ; unknown opcode 0x23\n.word 0x0A01 ; ROL with unused bits set\nBRA :L03\n:L03\n"
+    );
+
+    assert_eq!(disassemble(&[0; 3]), Err(DisassembleError::OddLength(3)));
+    assert_eq!(disassemble(&[0; 514]), Err(DisassembleError::TooLarge(514)));
+}
+
+#[test]
+fn test_disassemble_round_trip() {
+    // Every opcode with varied operands, filling a whole ROM image
+    let mut state = 0x1234_5678_u32;
+    let image = (0..512)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .enumerate()
+        .map(|(index, byte)| if index % 2 == 0 { byte % 0x48 } else { byte })
+        .collect::<Vec<_>>();
+
+    let source = disassemble(&image).unwrap();
+    assert_eq!(assemble(&source).unwrap().to_bytes(), image);
+
+    let full_range = (0..=u16::MAX)
+        .step_by(257)
+        .flat_map(u16::to_be_bytes)
+        .collect::<Vec<_>>();
+    let source = disassemble(&full_range).unwrap();
+    assert_eq!(assemble(&source).unwrap().to_bytes(), full_range);
+}
@@ -0,0 +1,110 @@
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
+
+use nano_chip_emulator::{load_program, LoadedProgram, TestCase, TestSpec};
+
+const USAGE: &str = "Usage : nano_chip_test spec.test|program.asm [more...]";
+
+/// Number of passed and failed tests
+#[derive(Clone, Copy, Default)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+}
+
+fn load(program_path: &Path) -> Result<LoadedProgram, String> {
+    load_program(program_path)
+        .map_err(|load_error| load_error.render(std::io::stdout().is_terminal()))
+}
+
+/// Run test cases given as their line and text, `file` is the file they come from
+fn run_cases(file: &Path, program: &LoadedProgram, cases: &[(usize, String)]) -> Summary {
+    let mut summary = Summary::default();
+
+    for (line, text) in cases {
+        match TestCase::parse(*line, text, &program.symbols) {
+            Ok(case) => {
+                let result = case.run(&program.emulator);
+                println!("{result}");
+
+                if result.passed() {
+                    summary.passed += 1;
+                } else {
+                    summary.failed += 1;
+                }
+            }
+            Err(spec_error) => {
+                println!("FAIL {} {spec_error}", file.display());
+                summary.failed += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+/// Run every test case of a spec file, or the `;@test` annotations of an assembly source
+fn run_file(file: &Path) -> Result<Summary, String> {
+    if file
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("asm"))
+    {
+        let program = load(file)?;
+
+        if program.tests.is_empty() {
+            return Err(format!("no ;@test annotation in {}", file.display()));
+        }
+
+        return Ok(run_cases(file, &program, &program.tests));
+    }
+
+    let text = std::fs::read_to_string(file)
+        .map_err(|read_error| format!("can't read {} : {read_error}", file.display()))?;
+
+    let spec =
+        TestSpec::parse(&text).map_err(|spec_error| format!("{} {spec_error}", file.display()))?;
+
+    let program_path = file
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(&spec.program);
+
+    Ok(run_cases(file, &load(&program_path)?, &spec.cases))
+}
+
+fn main() -> ExitCode {
+    let files = std::env::args().skip(1).collect::<Vec<_>>();
+
+    if files.is_empty() {
+        println!("Error, a test spec or assembly file is needed as parameter");
+        println!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut summary = Summary::default();
+
+    for file in &files {
+        match run_file(Path::new(file)) {
+            Ok(file_summary) => {
+                summary.passed += file_summary.passed;
+                summary.failed += file_summary.failed;
+            }
+            Err(message) => {
+                println!("Error, {message}");
+                summary.failed += 1;
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", summary.passed, summary.failed);
+
+    if summary.failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
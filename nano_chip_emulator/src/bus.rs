@@ -0,0 +1,194 @@
+use std::any::Any;
+use std::fmt;
+
+use nano_chip_isa::RAM_SIZE;
+
+/// Peripheral mapped over a range of RAM addresses
+///
+/// Offsets are relative to the first address of the device
+pub trait Device: fmt::Debug {
+    /// Kind of device, as written in memory map files
+    fn name(&self) -> &'static str;
+
+    /// Number of addresses used by the device
+    fn size(&self) -> usize;
+
+    /// Value seen by the CPU, reading never changes the device
+    fn read(&self, offset: usize) -> u8;
+
+    fn write(&mut self, offset: usize, value: u8);
+
+    /// Put the device back in its power on state
+    fn reset(&mut self);
+
+    /// Current state for the user, like the lit LEDs or the printed text
+    fn display(&self) -> String;
+
+    /// Bytes holding the whole state of the device, for snapshots
+    fn save_state(&self) -> Vec<u8>;
+
+    /// Restore a state given by `save_state`, returns false if it isn't valid for this device
+    fn load_state(&mut self, state: &[u8]) -> bool;
+
+    fn clone_box(&self) -> Box<dyn Device>;
+
+    /// Access to the concrete device, to set switches or read the console output
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl Clone for Box<dyn Device> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BusError {
+    /// The device doesn't fit below the end of the RAM, holds its first address
+    OutOfRange(u8),
+    /// The device overlaps the device at the given address
+    Overlap(u8),
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange(start) => {
+                write!(f, "device at 0x{start:02X} goes past the end of the RAM")
+            }
+            Self::Overlap(start) => write!(f, "overlaps the device at 0x{start:02X}"),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
+
+/// A device and its first address
+#[derive(Clone, Debug)]
+pub struct Mapping {
+    pub start: u8,
+    pub device: Box<dyn Device>,
+}
+
+impl Mapping {
+    fn contains(&self, address: u8) -> bool {
+        (usize::from(self.start)..usize::from(self.start) + self.device.size())
+            .contains(&usize::from(address))
+    }
+}
+
+/// Address space of the CPU : the RAM, with devices mapped over some of its addresses
+#[derive(Clone, Debug)]
+pub struct Bus {
+    ram: [u8; RAM_SIZE],
+    mappings: Vec<Mapping>,
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus {
+    pub const fn new() -> Self {
+        Self {
+            ram: [0; RAM_SIZE],
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Route the addresses from `start` to `device`
+    pub fn map(&mut self, start: u8, device: Box<dyn Device>) -> Result<(), BusError> {
+        let end = usize::from(start) + device.size();
+
+        if end > RAM_SIZE {
+            return Err(BusError::OutOfRange(start));
+        }
+
+        if let Some(other) = self.mappings.iter().find(|other| {
+            usize::from(start) < usize::from(other.start) + other.device.size()
+                && usize::from(other.start) < end
+        }) {
+            return Err(BusError::Overlap(other.start));
+        }
+
+        self.mappings.push(Mapping { start, device });
+        self.mappings.sort_by_key(|mapping| mapping.start);
+
+        Ok(())
+    }
+
+    /// Mapped devices sorted by address
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    pub fn mappings_mut(&mut self) -> &mut [Mapping] {
+        &mut self.mappings
+    }
+
+    /// Device handling `address`, if any
+    pub fn device_at(&self, address: u8) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .find(|mapping| mapping.contains(address))
+    }
+
+    /// Plain RAM, the cells under a device keep the value they had before it was mapped
+    pub const fn ram(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+
+    /// Plain RAM, writing it bypasses the devices
+    pub const fn ram_mut(&mut self) -> &mut [u8; RAM_SIZE] {
+        &mut self.ram
+    }
+
+    pub fn read(&self, address: u8) -> u8 {
+        self.device_at(address).map_or_else(
+            || self.ram[usize::from(address)],
+            |mapping| mapping.device.read(usize::from(address - mapping.start)),
+        )
+    }
+
+    pub fn write(&mut self, address: u8, value: u8) {
+        match self
+            .mappings
+            .iter_mut()
+            .find(|mapping| mapping.contains(address))
+        {
+            Some(mapping) => mapping
+                .device
+                .write(usize::from(address - mapping.start), value),
+            None => self.ram[usize::from(address)] = value,
+        }
+    }
+
+    /// One line per device with its address, kind and state, empty when no device is mapped
+    pub fn device_states(&self) -> String {
+        self.mappings
+            .iter()
+            .map(|mapping| {
+                format!(
+                    "0x{:02X}  {:<13}  {}",
+                    mapping.start,
+                    mapping.device.name(),
+                    mapping.device.display()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Clear the RAM and reset every device, the memory map is kept
+    pub fn reset(&mut self) {
+        self.ram = [0; RAM_SIZE];
+
+        for mapping in &mut self.mappings {
+            mapping.device.reset();
+        }
+    }
+}
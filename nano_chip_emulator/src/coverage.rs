@@ -0,0 +1,350 @@
+use std::fmt::Write;
+
+use nano_chip_isa::ROM_SIZE;
+
+use crate::value::{labels, Symbols};
+use crate::StepRecord;
+
+/// Instructions written with `.word` are data, they don't count as code to cover
+fn is_data(source_line: Option<&(usize, String)>) -> bool {
+    source_line.is_some_and(|(_, text)| {
+        text.trim_start()
+            .get(..5)
+            .is_some_and(|directive| directive.eq_ignore_ascii_case(".word"))
+    })
+}
+
+fn percent(hit: u64, total: u64) -> String {
+    (hit * 100)
+        .checked_div(total)
+        .map_or_else(|| "-".to_owned(), |percent| format!("{percent}%"))
+}
+
+/// Executions of each ROM address and outcomes of the conditional branches, filled with `record`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coverage {
+    executed: [u64; ROM_SIZE],
+    taken: [u64; ROM_SIZE],
+    not_taken: [u64; ROM_SIZE],
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub const fn new() -> Self {
+        Self {
+            executed: [0; ROM_SIZE],
+            taken: [0; ROM_SIZE],
+            not_taken: [0; ROM_SIZE],
+        }
+    }
+
+    /// Count an executed instruction, for `run_to_halt_with`
+    pub fn record(&mut self, step: &StepRecord) {
+        let address = usize::from(step.pc);
+        self.executed[address] += 1;
+
+        let condition =
+            nano_chip_isa::decode(step.word).and_then(|(info, _)| info.mnemonic.branch_condition());
+
+        if let Some((flag, value)) = condition {
+            if step.flags_before.get(flag) == value {
+                self.taken[address] += 1;
+            } else {
+                self.not_taken[address] += 1;
+            }
+        }
+    }
+
+    /// Number of times the instruction at `address` was executed
+    pub const fn executed(&self, address: u8) -> u64 {
+        self.executed[address as usize]
+    }
+
+    /// Number of times the conditional branch at `address` was taken and not taken
+    pub const fn branch(&self, address: u8) -> (u64, u64) {
+        (
+            self.taken[address as usize],
+            self.not_taken[address as usize],
+        )
+    }
+
+    /// Number of ROM words of the program : the source lines if there are some, otherwise up to the
+    /// last word that isn't 0
+    fn program_length(rom: &[u16; ROM_SIZE], source_lines: &[(usize, String)]) -> usize {
+        if source_lines.is_empty() {
+            rom.iter()
+                .rposition(|word| *word != 0)
+                .map_or(0, |last| last + 1)
+        } else {
+            source_lines.len()
+        }
+    }
+
+    /// Addresses holding code, data words excluded
+    fn code(rom: &[u16; ROM_SIZE], source_lines: &[(usize, String)]) -> Vec<u8> {
+        (0..Self::program_length(rom, source_lines))
+            .filter(|&address| !is_data(source_lines.get(address)))
+            .filter_map(|address| u8::try_from(address).ok())
+            .collect()
+    }
+
+    /// Whether the word at `address` is a conditional branch
+    fn is_conditional(rom: &[u16; ROM_SIZE], address: u8) -> bool {
+        nano_chip_isa::decode(rom[usize::from(address)])
+            .is_some_and(|(info, _)| info.mnemonic.branch_condition().is_some())
+    }
+
+    /// Outcome of the branch at `address`, like `both 3/1` with the taken and not taken counts
+    fn branch_state(&self, address: u8) -> String {
+        let (taken, not_taken) = self.branch(address);
+
+        let state = match (taken > 0, not_taken > 0) {
+            (true, true) => "both",
+            (true, false) => "taken",
+            (false, true) => "not taken",
+            (false, false) => "never",
+        };
+
+        format!("{state} {taken}/{not_taken}")
+    }
+
+    /// Every instruction of the program with its execution count and branch outcomes, `#####` marks
+    /// the instructions never executed
+    ///
+    /// The source lines are shown when given, the disassembly otherwise
+    pub fn listing(
+        &self,
+        rom: &[u16; ROM_SIZE],
+        source_lines: &[(usize, String)],
+        symbols: &Symbols,
+    ) -> String {
+        let labels = labels(symbols);
+        let mut listing = String::new();
+
+        let _ = writeln!(
+            listing,
+            "   Count  Addr  Branch taken/not      Line  Source"
+        );
+
+        for address in 0..Self::program_length(rom, source_lines) {
+            let Ok(address) = u8::try_from(address) else {
+                break;
+            };
+
+            for (_, label) in labels.iter().filter(|(start, _)| *start == address) {
+                let _ = writeln!(listing, "{label}");
+            }
+
+            let count = match self.executed(address) {
+                0 => "#####".to_owned(),
+                count => count.to_string(),
+            };
+
+            let branch = if Self::is_conditional(rom, address) {
+                self.branch_state(address)
+            } else {
+                String::new()
+            };
+
+            let (line, text) = source_lines.get(usize::from(address)).map_or_else(
+                || {
+                    let word = rom[usize::from(address)];
+                    let text = nano_chip_isa::decode(word).map_or_else(
+                        || format!(".word 0x{word:04X}"),
+                        |(info, operand)| info.format(operand),
+                    );
+                    (String::new(), text)
+                },
+                |(line, text)| (line.to_string(), text.trim().to_owned()),
+            );
+
+            let _ = writeln!(
+                listing,
+                "{count:>8}  0x{address:02X}  {branch:<20}  {line:>4}  {text}"
+            );
+        }
+
+        listing
+    }
+
+    /// Executed instructions and covered branch outcomes for each label, the code before the first
+    /// label is counted as `(start)`
+    pub fn summary(
+        &self,
+        rom: &[u16; ROM_SIZE],
+        source_lines: &[(usize, String)],
+        symbols: &Symbols,
+    ) -> String {
+        let labels = labels(symbols);
+        let code = Self::code(rom, source_lines);
+
+        let mut regions = Vec::new();
+        if labels
+            .first()
+            .is_none_or(|(start, _)| code.first() < Some(start))
+        {
+            regions.push((0, "(start)"));
+        }
+        regions.extend(labels.iter().copied());
+
+        let name_width = regions
+            .iter()
+            .map(|(_, name)| name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .max("Label".len());
+
+        let mut summary = String::new();
+        let _ = writeln!(
+            summary,
+            "{:<name_width$}  Instructions        Branch outcomes",
+            "Label"
+        );
+
+        let mut write_row = |name: &str, addresses: &[u8]| {
+            let instructions = addresses.len() as u64;
+            let executed = addresses
+                .iter()
+                .filter(|&&address| self.executed(address) > 0)
+                .count() as u64;
+
+            let branches = addresses
+                .iter()
+                .filter(|&&address| Self::is_conditional(rom, address));
+            let outcomes = branches.clone().count() as u64 * 2;
+            let covered = branches
+                .map(|&address| {
+                    let (taken, not_taken) = self.branch(address);
+                    u64::from(taken > 0) + u64::from(not_taken > 0)
+                })
+                .sum::<u64>();
+
+            let _ = writeln!(
+                summary,
+                "{name:<name_width$}  {:<9} {:>5}     {:<9} {:>5}",
+                format!("{executed}/{instructions}"),
+                percent(executed, instructions),
+                format!("{covered}/{outcomes}"),
+                percent(covered, outcomes)
+            );
+        };
+
+        for (index, (start, name)) in regions.iter().enumerate() {
+            let end = regions
+                .get(index + 1)
+                .map_or(ROM_SIZE, |(end, _)| usize::from(*end));
+
+            let addresses = code
+                .iter()
+                .copied()
+                .filter(|&address| address >= *start && usize::from(address) < end)
+                .collect::<Vec<_>>();
+
+            write_row(name, &addresses);
+        }
+
+        write_row("Total", &code);
+        summary
+    }
+
+    /// Coverage in the lcov tracefile format, for `genhtml` and code review tools
+    ///
+    /// Lines come from `source_lines`, labels are reported as functions and each conditional branch
+    /// has a taken and a not taken outcome
+    pub fn lcov(
+        &self,
+        rom: &[u16; ROM_SIZE],
+        source_lines: &[(usize, String)],
+        symbols: &Symbols,
+        source_path: &str,
+    ) -> String {
+        let code = Self::code(rom, source_lines);
+        let line_of = |address: u8| {
+            source_lines
+                .get(usize::from(address))
+                .map(|(line, _)| *line)
+        };
+
+        let mut lcov = String::new();
+        let _ = writeln!(lcov, "TN:");
+        let _ = writeln!(lcov, "SF:{source_path}");
+
+        let functions = labels(symbols)
+            .into_iter()
+            .filter_map(|(address, name)| {
+                Some((line_of(address)?, name.trim_start_matches(':'), address))
+            })
+            .collect::<Vec<_>>();
+
+        for (line, name, _) in &functions {
+            let _ = writeln!(lcov, "FN:{line},{name}");
+        }
+
+        for (_, name, address) in &functions {
+            let _ = writeln!(lcov, "FNDA:{},{name}", self.executed(*address));
+        }
+
+        let _ = writeln!(lcov, "FNF:{}", functions.len());
+        let _ = writeln!(
+            lcov,
+            "FNH:{}",
+            functions
+                .iter()
+                .filter(|(_, _, address)| self.executed(*address) > 0)
+                .count()
+        );
+
+        let mut branches = 0;
+        let mut branches_hit = 0;
+
+        for &address in code
+            .iter()
+            .filter(|&&address| Self::is_conditional(rom, address))
+        {
+            let Some(line) = line_of(address) else {
+                continue;
+            };
+            let (taken, not_taken) = self.branch(address);
+
+            for (branch, count) in [taken, not_taken].into_iter().enumerate() {
+                let count = if self.executed(address) == 0 {
+                    "-".to_owned()
+                } else {
+                    count.to_string()
+                };
+
+                let _ = writeln!(lcov, "BRDA:{line},0,{branch},{count}");
+            }
+
+            branches += 2;
+            branches_hit += usize::from(taken > 0) + usize::from(not_taken > 0);
+        }
+
+        let _ = writeln!(lcov, "BRF:{branches}");
+        let _ = writeln!(lcov, "BRH:{branches_hit}");
+
+        let lines = code
+            .iter()
+            .filter_map(|&address| Some((line_of(address)?, self.executed(address))))
+            .collect::<Vec<_>>();
+
+        for (line, count) in &lines {
+            let _ = writeln!(lcov, "DA:{line},{count}");
+        }
+
+        let _ = writeln!(lcov, "LF:{}", lines.len());
+        let _ = writeln!(
+            lcov,
+            "LH:{}",
+            lines.iter().filter(|(_, count)| *count > 0).count()
+        );
+        let _ = writeln!(lcov, "end_of_record");
+
+        lcov
+    }
+}
@@ -0,0 +1,632 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+use std::path::Path;
+
+use nano_chip_isa::RAM_SIZE;
+
+use crate::value::{constant_at, format_instruction, label_at, parse_value, Symbols, ValueError};
+use crate::{
+    ConditionError, NanoChipEmulator, Snapshot, StepResult, Trap, TrapPolicies, WatchKind,
+    Watchpoints,
+};
+
+/// Number of instructions `continue` executes before giving up when no breakpoint is reached
+pub const CONTINUE_LIMIT: u64 = 1_000_000;
+
+/// Number of executed instructions the debugger can undo
+pub const HISTORY_LIMIT: usize = 100_000;
+
+const HELP: &str = "\
+step [n]             execute n instructions, 1 by default
+continue             run until a breakpoint or watchpoint
+step-back [n]        undo n instructions, 1 by default
+reverse-continue     go back to the previous breakpoint
+last-write [addr]    go back to the instruction that last wrote the RAM cell
+break [addr|label]   add a breakpoint, or list them without argument
+delete [addr|label]  remove a breakpoint, or all of them without argument
+watch [addr]         stop when an instruction writes the RAM cell
+watch condition      stop when the condition turns true, like [0x10] == 5 or acc == 0 && c
+watch                list the watchpoints with their hit counts
+unwatch [n]          remove watchpoint n, or all of them without argument
+ignore n count       let watchpoint n trigger count times without stopping
+print acc|flags|pc|[addr]
+set acc=v|pc=v|[addr]=v
+mem [start[..end]]   show RAM, 16 bytes from start or the range start..end
+reset                put the CPU back in its power on state
+devices              show the state of the memory mapped devices
+save file            save a snapshot of the CPU, RAM and devices
+load file            restore a snapshot taken with the same program
+quit
+An empty line repeats the last command, values are numbers (42, 0x2A, 0b101010) or symbols ($const, :label)";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebuggerError {
+    UnknownCommand(String),
+    /// Holds the usage of the command
+    Usage(&'static str),
+    Value(ValueError),
+    /// A snapshot couldn't be saved or loaded, holds the reason
+    Snapshot(String),
+    Condition(ConditionError),
+    /// No watchpoint has this number
+    UnknownWatchpoint(usize),
+}
+
+impl fmt::Display for DebuggerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(command) => {
+                write!(f, "unknown command `{command}`, type `help` for a list")
+            }
+            Self::Usage(usage) => write!(f, "usage : {usage}"),
+            Self::Value(value_error) => write!(f, "{value_error}"),
+            Self::Snapshot(message) => write!(f, "{message}"),
+            Self::Condition(condition_error) => write!(f, "{condition_error}"),
+            Self::UnknownWatchpoint(id) => write!(f, "no watchpoint {id}"),
+        }
+    }
+}
+
+impl std::error::Error for DebuggerError {}
+
+/// What the front end should do after a command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reply {
+    /// Text to show, may be empty
+    Output(String),
+    Quit,
+}
+
+/// Why execution stopped before running every requested instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    Breakpoint,
+    /// Holds the number of the watchpoint
+    Watchpoint(usize),
+    Trap(Trap),
+}
+
+/// Command line debugger driving a `NanoChipEmulator`
+pub struct Debugger {
+    emulator: NanoChipEmulator,
+    breakpoints: BTreeSet<u8>,
+    watchpoints: Watchpoints,
+    symbols: Symbols,
+    /// Name of the source file shown in the location
+    source_file: String,
+    /// Source line of each ROM word, by address
+    source_lines: Vec<usize>,
+    last_command: String,
+}
+
+impl Debugger {
+    /// Every trap of the emulator is reported so that execution stops on it, and the last
+    /// `HISTORY_LIMIT` instructions are recorded to step back
+    pub fn new(mut emulator: NanoChipEmulator) -> Self {
+        emulator.set_trap_policies(TrapPolicies::STOP);
+        emulator.set_history_limit(HISTORY_LIMIT);
+
+        Self {
+            emulator,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Watchpoints::new(),
+            symbols: Symbols::new(),
+            source_file: String::new(),
+            source_lines: Vec::new(),
+            last_command: String::new(),
+        }
+    }
+
+    /// Make a constant or label usable in commands, `name` includes its `$` or `:` prefix
+    pub fn add_symbol(&mut self, name: &str, value: u8) {
+        self.symbols.insert(name.to_owned(), value);
+    }
+
+    pub fn add_symbols(&mut self, symbols: &Symbols) {
+        self.symbols
+            .extend(symbols.iter().map(|(name, value)| (name.clone(), *value)));
+    }
+
+    /// Show the source position of the current instruction, `lines` gives the line of each ROM word
+    pub fn set_source(&mut self, file_name: &str, lines: Vec<usize>) {
+        file_name.clone_into(&mut self.source_file);
+        self.source_lines = lines;
+    }
+
+    pub const fn emulator(&self) -> &NanoChipEmulator {
+        &self.emulator
+    }
+
+    pub const fn emulator_mut(&mut self) -> &mut NanoChipEmulator {
+        &mut self.emulator
+    }
+
+    pub const fn breakpoints(&self) -> &BTreeSet<u8> {
+        &self.breakpoints
+    }
+
+    pub const fn breakpoints_mut(&mut self) -> &mut BTreeSet<u8> {
+        &mut self.breakpoints
+    }
+
+    pub const fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub const fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
+    }
+
+    /// Add the breakpoint, or remove it if there is one, returns whether there is a breakpoint now
+    pub fn toggle_breakpoint(&mut self, address: u8) -> bool {
+        self.breakpoints.remove(&address) || self.breakpoints.insert(address)
+    }
+
+    /// First label, in alphabetical order, pointing to `address`
+    pub fn label_at(&self, address: u8) -> Option<&str> {
+        label_at(&self.symbols, address)
+    }
+
+    /// Assembler syntax of the ROM word at `address`, with the names of branch targets and RAM
+    /// addresses
+    pub fn instruction_at(&self, address: u8) -> String {
+        let word = self.emulator.rom()[usize::from(address)];
+
+        format_instruction(word, &self.symbols)
+            .unwrap_or_else(|| format!("unknown opcode, word 0x{word:04X}"))
+    }
+
+    /// ROM address with its label, like `0x03 <:loop_start>`
+    fn rom_name(&self, address: u8) -> String {
+        self.label_at(address).map_or_else(
+            || format!("0x{address:02X}"),
+            |label| format!("0x{address:02X} <{label}>"),
+        )
+    }
+
+    /// RAM address with the constant naming it, like `[0x10] <$counter>`
+    fn ram_name(&self, address: u8) -> String {
+        constant_at(&self.symbols, address).map_or_else(
+            || format!("[0x{address:02X}]"),
+            |constant| format!("[0x{address:02X}] <{constant}>"),
+        )
+    }
+
+    /// Program counter and the instruction it points to, with the label at this address and the
+    /// source position if known
+    pub fn location(&self) -> String {
+        let pc = self.emulator.pc();
+        let mut location = format!("{}  {}", self.rom_name(pc), self.instruction_at(pc));
+
+        if let Some(line) = self.source_lines.get(usize::from(pc)) {
+            let _ = write!(location, "  ({}:{line})", self.source_file);
+        }
+
+        location
+    }
+
+    /// Run one command line, an empty line repeats the last command
+    pub fn execute(&mut self, line: &str) -> Result<Reply, DebuggerError> {
+        let line = line.trim();
+
+        let line = if line.is_empty() {
+            self.last_command.clone()
+        } else {
+            line.clone_into(&mut self.last_command);
+            line.to_owned()
+        };
+
+        let (command, argument) = line
+            .split_once(char::is_whitespace)
+            .map_or((line.as_str(), ""), |(command, argument)| {
+                (command, argument.trim())
+            });
+
+        let output = match command {
+            "" => String::new(),
+            "step" | "s" => self.step(argument)?,
+            "continue" | "c" => self.run_report(CONTINUE_LIMIT, true),
+            "step-back" | "sb" => self.step_back(argument)?,
+            "reverse-continue" | "rc" => self.reverse_continue(),
+            "last-write" | "lw" => self.last_write(argument)?,
+            "break" | "b" => self.add_breakpoint(argument)?,
+            "delete" | "d" => self.delete_breakpoint(argument)?,
+            "watch" | "w" => self.watch(argument)?,
+            "unwatch" => self.unwatch(argument)?,
+            "ignore" => self.ignore(argument)?,
+            "print" | "p" => self.print(argument)?,
+            "set" => self.set(argument)?,
+            "mem" | "m" => self.mem(argument)?,
+            "reset" => {
+                self.emulator.reset();
+                self.location()
+            }
+            "devices" => {
+                let states = self.emulator.bus().device_states();
+
+                if states.is_empty() {
+                    "No devices".to_owned()
+                } else {
+                    states
+                }
+            }
+            "save" => self.save(argument)?,
+            "load" => self.load(argument)?,
+            "help" | "h" => HELP.to_owned(),
+            "quit" | "q" => return Ok(Reply::Quit),
+            _ => return Err(DebuggerError::UnknownCommand(command.to_owned())),
+        };
+
+        Ok(Reply::Output(output))
+    }
+
+    fn value(&self, text: &str) -> Result<u8, DebuggerError> {
+        parse_value(text, &self.symbols).map_err(DebuggerError::Value)
+    }
+
+    /// Address between brackets, like `[0x10]` or `[$counter]`
+    fn address(&self, text: &str) -> Option<Result<u8, DebuggerError>> {
+        text.trim()
+            .strip_prefix('[')
+            .and_then(|inner| inner.strip_suffix(']'))
+            .map(|inner| self.value(inner))
+    }
+
+    /// Execute up to `count` instructions, stopping on breakpoints after the first one and on
+    /// watchpoints after the instruction that triggered them
+    pub fn run(&mut self, count: u64) -> Result<(), Stop> {
+        self.watchpoints.sync(&self.emulator);
+
+        for executed in 0..count {
+            if executed > 0 && self.breakpoints.contains(&self.emulator.pc()) {
+                return Err(Stop::Breakpoint);
+            }
+
+            if let StepResult::Trap(trap) = self.emulator.tick() {
+                return Err(Stop::Trap(trap));
+            }
+
+            if let Some(id) = self.watchpoints.check(&self.emulator) {
+                return Err(Stop::Watchpoint(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run and describe where execution stopped, `until_breakpoint` tells that reaching `count` is unusual
+    fn run_report(&mut self, count: u64, until_breakpoint: bool) -> String {
+        let mut output = match self.run(count) {
+            Ok(()) if until_breakpoint => {
+                format!("No breakpoint reached after {count} instructions\n")
+            }
+            Ok(()) => String::new(),
+            Err(Stop::Breakpoint) => format!("Breakpoint at 0x{:02X}\n", self.emulator.pc()),
+            Err(Stop::Watchpoint(id)) => format!("{}\n", self.watchpoint_hit(id)),
+            Err(Stop::Trap(trap)) => format!("Stopped, {trap}\n"),
+        };
+
+        output.push_str(&self.location());
+        output
+    }
+
+    /// Why watchpoint `id` stopped execution, with the write that triggered it
+    pub fn watchpoint_hit(&self, id: usize) -> String {
+        let Some(watchpoint) = self.watchpoints.get(id) else {
+            return format!("Watchpoint {id}");
+        };
+
+        let write = self.emulator.last_step().and_then(|step| step.ram_write);
+
+        match (&watchpoint.kind, write) {
+            (WatchKind::Write(_), Some(write)) => format!(
+                "Watchpoint {id}, [0x{:02X}] 0x{:02X} -> 0x{:02X}",
+                write.address, write.old_value, write.new_value
+            ),
+            (kind, _) => format!("Watchpoint {id}, {kind}"),
+        }
+    }
+
+    fn step(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        let count = if argument.is_empty() {
+            1
+        } else {
+            argument
+                .parse()
+                .map_err(|_| DebuggerError::Usage("step [n]"))?
+        };
+
+        Ok(self.run_report(count, false))
+    }
+
+    fn step_back(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        let count = if argument.is_empty() {
+            1
+        } else {
+            argument
+                .parse()
+                .map_err(|_| DebuggerError::Usage("step-back [n]"))?
+        };
+
+        let mut output = String::new();
+
+        for undone in 0..count {
+            if self.emulator.step_back().is_none() {
+                output = format!("Start of the history, {undone} instruction(s) undone\n");
+                break;
+            }
+        }
+
+        output.push_str(&self.location());
+        Ok(output)
+    }
+
+    /// Step back until the program counter is on a breakpoint, at least one instruction is undone
+    fn reverse_continue(&mut self) -> String {
+        let mut output = loop {
+            if self.emulator.step_back().is_none() {
+                break "Start of the history\n".to_owned();
+            }
+
+            let pc = self.emulator.pc();
+
+            if self.breakpoints.contains(&pc) {
+                break format!("Breakpoint at 0x{pc:02X}\n");
+            }
+        };
+
+        output.push_str(&self.location());
+        output
+    }
+
+    /// Step back to the instruction that last wrote a RAM cell, nothing is undone if the history has no such write
+    fn last_write(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        let address = self
+            .address(argument)
+            .ok_or(DebuggerError::Usage("last-write [addr]"))??;
+
+        let Some(index) = self
+            .emulator
+            .history()
+            .iter()
+            .rposition(|step| step.ram_write.is_some_and(|write| write.address == address))
+        else {
+            return Ok(format!("No write to [0x{address:02X}] in the history"));
+        };
+
+        let mut write = None;
+
+        while self.emulator.history().len() > index {
+            write = self.emulator.step_back().and_then(|step| step.ram_write);
+        }
+
+        let mut output = String::new();
+
+        if let Some(write) = write {
+            let _ = writeln!(
+                output,
+                "[0x{address:02X}] 0x{:02X} -> 0x{:02X} at cycle {}",
+                write.old_value,
+                write.new_value,
+                self.emulator.cycles()
+            );
+        }
+
+        output.push_str(&self.location());
+        Ok(output)
+    }
+
+    fn save(&self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            return Err(DebuggerError::Usage("save file"));
+        }
+
+        self.emulator
+            .snapshot()
+            .save(Path::new(argument))
+            .map_err(|write_error| {
+                DebuggerError::Snapshot(format!("can't write {argument} : {write_error}"))
+            })?;
+
+        Ok(format!("Saved to {argument}"))
+    }
+
+    fn load(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            return Err(DebuggerError::Usage("load file"));
+        }
+
+        Snapshot::load(Path::new(argument))
+            .and_then(|snapshot| self.emulator.restore(&snapshot))
+            .map_err(|snapshot_error| {
+                DebuggerError::Snapshot(format!("{argument} {snapshot_error}"))
+            })?;
+
+        Ok(self.location())
+    }
+
+    fn add_breakpoint(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            if self.breakpoints.is_empty() {
+                return Ok("No breakpoints".to_owned());
+            }
+
+            return Ok(self
+                .breakpoints
+                .iter()
+                .map(|&address| format!("Breakpoint at {}", self.rom_name(address)))
+                .collect::<Vec<_>>()
+                .join("\n"));
+        }
+
+        let address = self.value(argument)?;
+        self.breakpoints.insert(address);
+
+        Ok(format!("Breakpoint at {}", self.rom_name(address)))
+    }
+
+    fn delete_breakpoint(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            let count = self.breakpoints.len();
+            self.breakpoints.clear();
+            return Ok(format!("Deleted {count} breakpoint(s)"));
+        }
+
+        let address = self.value(argument)?;
+
+        if self.breakpoints.remove(&address) {
+            Ok(format!("Deleted breakpoint at {}", self.rom_name(address)))
+        } else {
+            Ok(format!("No breakpoint at {}", self.rom_name(address)))
+        }
+    }
+
+    fn watch(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            if self.watchpoints.is_empty() {
+                return Ok("No watchpoints".to_owned());
+            }
+
+            return Ok(self
+                .watchpoints
+                .iter()
+                .map(|(id, watchpoint)| {
+                    let mut line = format!(
+                        "Watchpoint {id} : {}, {} hit(s)",
+                        watchpoint.kind, watchpoint.hits
+                    );
+
+                    if watchpoint.ignore > 0 {
+                        let _ = write!(line, ", ignoring the next {}", watchpoint.ignore);
+                    }
+
+                    line
+                })
+                .collect::<Vec<_>>()
+                .join("\n"));
+        }
+
+        let kind = WatchKind::parse(argument, &self.symbols).map_err(DebuggerError::Condition)?;
+        let description = kind.to_string();
+        let id = self.watchpoints.add(kind);
+
+        Ok(format!("Watchpoint {id} : {description}"))
+    }
+
+    /// Number of an existing watchpoint
+    fn watchpoint_id(&self, text: &str, usage: &'static str) -> Result<usize, DebuggerError> {
+        let id = text.parse().map_err(|_| DebuggerError::Usage(usage))?;
+
+        if self.watchpoints.get(id).is_none() {
+            return Err(DebuggerError::UnknownWatchpoint(id));
+        }
+
+        Ok(id)
+    }
+
+    fn unwatch(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        if argument.is_empty() {
+            let count = self.watchpoints.clear();
+            return Ok(format!("Deleted {count} watchpoint(s)"));
+        }
+
+        let id = self.watchpoint_id(argument, "unwatch [n]")?;
+        self.watchpoints.remove(id);
+
+        Ok(format!("Deleted watchpoint {id}"))
+    }
+
+    fn ignore(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        const USAGE: &str = "ignore n count";
+
+        let (id, count) = argument
+            .split_once(char::is_whitespace)
+            .ok_or(DebuggerError::Usage(USAGE))?;
+        let id = self.watchpoint_id(id, USAGE)?;
+        let count = count
+            .trim()
+            .parse()
+            .map_err(|_| DebuggerError::Usage(USAGE))?;
+
+        if let Some(watchpoint) = self.watchpoints.get_mut(id) {
+            watchpoint.ignore = count;
+        }
+
+        Ok(format!(
+            "Watchpoint {id} will ignore its next {count} hit(s)"
+        ))
+    }
+
+    fn print(&self, argument: &str) -> Result<String, DebuggerError> {
+        const USAGE: &str = "print acc|flags|pc|[addr]";
+
+        match argument {
+            "acc" => {
+                let acc = self.emulator.accumulator();
+                Ok(format!("acc = 0x{acc:02X} ({acc})"))
+            }
+            "flags" => Ok(format!("flags = {}", self.emulator.flags().letters())),
+            "pc" => Ok(self.location()),
+            _ => {
+                let address = self
+                    .address(argument)
+                    .ok_or(DebuggerError::Usage(USAGE))??;
+                Ok(self.ram_cell(address))
+            }
+        }
+    }
+
+    /// Value of a RAM cell, like `[0x10] <$counter> = 0x2A (42)`
+    fn ram_cell(&self, address: u8) -> String {
+        let value = self.emulator.read_ram(address);
+        format!("{} = 0x{value:02X} ({value})", self.ram_name(address))
+    }
+
+    fn set(&mut self, argument: &str) -> Result<String, DebuggerError> {
+        const USAGE: &str = "set acc=v|pc=v|[addr]=v";
+
+        let (target, value) = argument
+            .split_once('=')
+            .ok_or(DebuggerError::Usage(USAGE))?;
+        let target = target.trim();
+        let value = self.value(value)?;
+
+        match target {
+            "acc" => self.emulator.set_accumulator(value),
+            "pc" => self.emulator.set_pc(value),
+            _ => {
+                let address = self.address(target).ok_or(DebuggerError::Usage(USAGE))??;
+                self.emulator.write_ram(address, value);
+            }
+        }
+
+        Ok(String::new())
+    }
+
+    fn mem(&self, argument: &str) -> Result<String, DebuggerError> {
+        let (start, end) = if argument.is_empty() {
+            (0, RAM_SIZE)
+        } else if let Some((start, end)) = argument.split_once("..") {
+            let start = self.value(start)?;
+            let end = match end.strip_prefix('=') {
+                Some(end) => usize::from(self.value(end)?) + 1,
+                None => usize::from(self.value(end)?),
+            };
+            (start, end)
+        } else {
+            let start = self.value(argument)?;
+            (start, usize::from(start) + 16)
+        };
+
+        let mut output = self.emulator.ram_dump(start, end);
+
+        // Cells named by a constant are listed under the dump
+        for address in (usize::from(start)..end).filter_map(|address| u8::try_from(address).ok()) {
+            if constant_at(&self.symbols, address).is_some() {
+                let _ = write!(output, "\n{}", self.ram_cell(address));
+            }
+        }
+
+        Ok(output)
+    }
+}
@@ -0,0 +1,327 @@
+use std::any::Any;
+
+use crate::bus::Device;
+
+/// Bank of 8 LEDs, bit 0 is the rightmost LED
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Leds {
+    value: u8,
+}
+
+impl Leds {
+    pub const fn new() -> Self {
+        Self { value: 0 }
+    }
+
+    pub const fn value(&self) -> u8 {
+        self.value
+    }
+}
+
+impl Device for Leds {
+    fn name(&self) -> &'static str {
+        "leds"
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&self, _offset: usize) -> u8 {
+        self.value
+    }
+
+    fn write(&mut self, _offset: usize, value: u8) {
+        self.value = value;
+    }
+
+    fn reset(&mut self) {
+        self.value = 0;
+    }
+
+    fn display(&self) -> String {
+        (0..8)
+            .rev()
+            .map(|bit| {
+                if self.value >> bit & 1 == 1 {
+                    '●'
+                } else {
+                    '○'
+                }
+            })
+            .collect()
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.value]
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [value] => {
+                self.value = *value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Bank of 8 switches, the program reads their position and can't change it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Switches {
+    /// Position at power on
+    initial: u8,
+    value: u8,
+}
+
+impl Switches {
+    pub const fn new(initial: u8) -> Self {
+        Self {
+            initial,
+            value: initial,
+        }
+    }
+
+    pub const fn value(&self) -> u8 {
+        self.value
+    }
+
+    pub const fn set(&mut self, value: u8) {
+        self.value = value;
+    }
+}
+
+impl Device for Switches {
+    fn name(&self) -> &'static str {
+        "switches"
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&self, _offset: usize) -> u8 {
+        self.value
+    }
+
+    fn write(&mut self, _offset: usize, _value: u8) {}
+
+    fn reset(&mut self) {
+        self.value = self.initial;
+    }
+
+    fn display(&self) -> String {
+        format!("{:08b}", self.value)
+    }
+
+    /// The position at power on comes from the memory map, only the current one is saved
+    fn save_state(&self) -> Vec<u8> {
+        vec![self.value]
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [value] => {
+                self.value = *value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Segments lit for each hexadecimal digit, bit 0 is segment a and bit 6 is segment g
+const HEX_SEGMENTS: [u8; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+/// 7-segment display, one byte per digit with the segments in bits 0 (a) to 6 (g) and the dot in bit 7
+///
+/// The first address holds the leftmost digit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SevenSegment {
+    digits: Vec<u8>,
+}
+
+impl SevenSegment {
+    pub fn new(digit_count: usize) -> Self {
+        Self {
+            digits: vec![0; digit_count],
+        }
+    }
+
+    /// Segments of each digit
+    pub fn digits(&self) -> &[u8] {
+        &self.digits
+    }
+
+    /// Segments showing `digit` in hexadecimal, only the low 4 bits are used
+    pub const fn encode(digit: u8) -> u8 {
+        HEX_SEGMENTS[(digit & 0xF) as usize]
+    }
+}
+
+impl Device for SevenSegment {
+    fn name(&self) -> &'static str {
+        "seven_segment"
+    }
+
+    fn size(&self) -> usize {
+        self.digits.len()
+    }
+
+    fn read(&self, offset: usize) -> u8 {
+        self.digits[offset]
+    }
+
+    fn write(&mut self, offset: usize, value: u8) {
+        self.digits[offset] = value;
+    }
+
+    fn reset(&mut self) {
+        self.digits.fill(0);
+    }
+
+    /// Hexadecimal digits, blank when no segment is lit and `?` for other patterns
+    fn display(&self) -> String {
+        let mut text = String::new();
+
+        for segments in &self.digits {
+            let lit = segments & 0x7F;
+
+            text.push(
+                match HEX_SEGMENTS.iter().position(|pattern| *pattern == lit) {
+                    Some(digit) => char::from(b"0123456789ABCDEF"[digit]),
+                    None if lit == 0 => ' ',
+                    None => '?',
+                },
+            );
+
+            if segments & 0x80 != 0 {
+                text.push('.');
+            }
+        }
+
+        text
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.digits.clone()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        if state.len() != self.digits.len() {
+            return false;
+        }
+
+        self.digits.copy_from_slice(state);
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Text output, each written byte is an ASCII character, reading gives 0
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Console {
+    output: String,
+}
+
+impl Console {
+    pub const fn new() -> Self {
+        Self {
+            output: String::new(),
+        }
+    }
+
+    /// Every character written since the last reset
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+impl Device for Console {
+    fn name(&self) -> &'static str {
+        "console"
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn read(&self, _offset: usize) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: usize, value: u8) {
+        self.output.push(char::from(value));
+    }
+
+    fn reset(&mut self) {
+        self.output.clear();
+    }
+
+    fn display(&self) -> String {
+        format!("{:?}", self.output)
+    }
+
+    /// The written bytes, each character of the output comes from one byte
+    fn save_state(&self) -> Vec<u8> {
+        self.output
+            .chars()
+            .map(|character| u8::try_from(character).unwrap_or_default())
+            .collect()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        self.output = state.iter().copied().map(char::from).collect();
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn Device> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+use nano_chip_isa::{Flag, Flags, RAM_SIZE, ROM_SIZE};
+
+use crate::hex::{decode_hex, encode_hex};
+use crate::{Debugger, Stop, Trap};
+
+/// Target description served to GDB with `qXfer:features:read`, registers are `pc`, `acc` and `flags`
+pub const TARGET_XML: &str = include_str!("../gdb/target.xml");
+
+/// First GDB address of the ROM, each word takes two bytes from there, the RAM starts at 0
+pub const GDB_ROM_START: usize = 0x1_0000;
+
+/// Instructions executed by `c` between two checks for an interrupt from GDB
+const CONTINUE_CHUNK: u64 = 1000;
+
+/// Reply to a packet that can't be honored
+const ERROR: &str = "E01";
+
+/// Signals of the stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{signal:02x}")
+}
+
+/// Signal telling GDB why execution stopped
+const fn signal(stop: Stop) -> u8 {
+    match stop {
+        Stop::Breakpoint | Stop::Watchpoint(_) | Stop::Trap(Trap::Halted { .. }) => SIGTRAP,
+        Stop::Trap(Trap::IllegalOpcode { .. }) => SIGILL,
+        Stop::Trap(Trap::PcWrap) => SIGSEGV,
+    }
+}
+
+/// Packet as sent on the wire : `$payload#checksum`, with `#`, `$`, `}` and `*` escaped
+fn frame(payload: &str) -> Vec<u8> {
+    let mut framed = vec![b'$'];
+
+    for byte in payload.bytes() {
+        if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+            framed.extend([b'}', byte ^ 0x20]);
+        } else {
+            framed.push(byte);
+        }
+    }
+
+    let checksum = framed[1..]
+        .iter()
+        .fold(0_u8, |checksum, byte| checksum.wrapping_add(*byte));
+    framed.extend(format!("#{checksum:02x}").bytes());
+    framed
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+/// Flags as a byte, Z in bit 0 then C, V and N
+fn flags_byte(flags: Flags) -> u8 {
+    Flag::ALL.iter().enumerate().fold(0, |byte, (bit, flag)| {
+        byte | u8::from(flags.get(*flag)) << bit
+    })
+}
+
+fn byte_flags(byte: u8) -> Flags {
+    let mut flags = Flags::NONE;
+
+    for (bit, flag) in Flag::ALL.into_iter().enumerate() {
+        flags.set(flag, byte >> bit & 1 != 0);
+    }
+
+    flags
+}
+
+/// What GDB sent
+enum Incoming {
+    Packet {
+        payload: String,
+        valid: bool,
+    },
+    /// Ctrl-C, byte 0x03 outside of a packet
+    Interrupt,
+}
+
+/// Next byte from GDB, the ones read ahead by `interrupt_pending` come first
+fn read_byte(stream: &mut impl Read, pending: &mut VecDeque<u8>) -> io::Result<Option<u8>> {
+    if let Some(byte) = pending.pop_front() {
+        return Ok(Some(byte));
+    }
+
+    let mut byte = [0];
+
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Next packet or interrupt, acknowledgments are skipped, `None` once GDB disconnected
+fn read_incoming(
+    stream: &mut impl Read,
+    pending: &mut VecDeque<u8>,
+) -> io::Result<Option<Incoming>> {
+    loop {
+        match read_byte(stream, pending)? {
+            None => return Ok(None),
+            Some(0x03) => return Ok(Some(Incoming::Interrupt)),
+            Some(b'$') => break,
+            Some(_) => {}
+        }
+    }
+
+    let mut raw = Vec::new();
+
+    loop {
+        match read_byte(stream, pending)? {
+            None => return Ok(None),
+            Some(b'#') => break,
+            Some(byte) => raw.push(byte),
+        }
+    }
+
+    let (Some(high), Some(low)) = (read_byte(stream, pending)?, read_byte(stream, pending)?) else {
+        return Ok(None);
+    };
+
+    let checksum = raw
+        .iter()
+        .fold(0_u8, |checksum, byte| checksum.wrapping_add(*byte));
+    let valid = decode_hex(&String::from_utf8_lossy(&[high, low])) == Some(vec![checksum]);
+
+    let mut payload = Vec::with_capacity(raw.len());
+    let mut bytes = raw.into_iter();
+
+    while let Some(byte) = bytes.next() {
+        payload.push(match byte {
+            b'}' => bytes.next().unwrap_or_default() ^ 0x20,
+            _ => byte,
+        });
+    }
+
+    Ok(Some(Incoming::Packet {
+        payload: String::from_utf8_lossy(&payload).into_owned(),
+        valid,
+    }))
+}
+
+/// Whether GDB sent an interrupt, or disconnected, while the program runs
+///
+/// The other bytes received are kept in `pending` for `read_incoming`, so that a packet sent early
+/// isn't lost
+fn interrupt_pending(stream: &TcpStream, pending: &mut VecDeque<u8>) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+
+    let mut buffer = [0; 64];
+    let mut interrupted = false;
+
+    loop {
+        match (&*stream).read(&mut buffer) {
+            Ok(0) => {
+                interrupted = true;
+                break;
+            }
+            Ok(read) => {
+                for &byte in &buffer[..read] {
+                    if byte == 0x03 {
+                        interrupted = true;
+                    } else {
+                        pending.push_back(byte);
+                    }
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    let _ = stream.set_nonblocking(false);
+    interrupted
+}
+
+/// Server side of the GDB Remote Serial Protocol, driving a `Debugger`
+///
+/// Registers are `pc`, `acc` and `flags`, one byte each. Memory holds the RAM at 0 and the ROM at
+/// `GDB_ROM_START`, only the RAM can be written. Breakpoints (`Z0` and `Z1`) use ROM word addresses,
+/// like the program counter
+pub struct GdbStub {
+    debugger: Debugger,
+    /// GDB asked with `QStartNoAckMode` not to acknowledge packets anymore
+    no_ack: bool,
+    /// GDB detached or killed the program
+    finished: bool,
+}
+
+impl GdbStub {
+    pub const fn new(debugger: Debugger) -> Self {
+        Self {
+            debugger,
+            no_ack: false,
+            finished: false,
+        }
+    }
+
+    pub const fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub const fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    /// GDB detached or killed the program, the session is over
+    pub const fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Reply to the payload of a packet, an empty reply tells GDB the packet isn't supported
+    ///
+    /// `interrupted` is polled while `c` runs, execution stops when it returns true
+    pub fn answer(&mut self, packet: &str, interrupted: impl FnMut() -> bool) -> String {
+        let command = packet.chars().next().unwrap_or_default();
+        let arguments = packet.get(command.len_utf8()..).unwrap_or_default();
+
+        let reply = match command {
+            '?' => Some(stop_reply(SIGTRAP)),
+            'g' => Some(encode_hex(&self.registers())),
+            'G' => self.write_registers(arguments),
+            'p' => parse_hex(arguments)
+                .and_then(|register| self.registers().get(register).copied())
+                .map(|value| encode_hex(&[value])),
+            'P' => self.write_register(arguments),
+            'm' => self.read_memory(arguments),
+            'M' => self.write_memory(arguments),
+            's' => self.resume(arguments, |stub| {
+                stub.debugger
+                    .run(1)
+                    .map_or_else(|stop| stop_reply(signal(stop)), |()| stop_reply(SIGTRAP))
+            }),
+            'c' => self.resume(arguments, |stub| stub.continue_until_stop(interrupted)),
+            'Z' | 'z' => self.breakpoint(command == 'Z', arguments),
+            'D' => {
+                self.finished = true;
+                Some("OK".to_owned())
+            }
+            'k' => {
+                self.finished = true;
+                Some(String::new())
+            }
+            _ => Some(self.query(packet)),
+        };
+
+        reply.unwrap_or_else(|| ERROR.to_owned())
+    }
+
+    /// `pc`, `acc` and `flags`, in the order of the target description
+    fn registers(&self) -> [u8; 3] {
+        let emulator = self.debugger.emulator();
+        [
+            emulator.pc(),
+            emulator.accumulator(),
+            flags_byte(emulator.flags()),
+        ]
+    }
+
+    fn set_register(&mut self, register: usize, value: u8) -> Option<()> {
+        let emulator = self.debugger.emulator_mut();
+
+        match register {
+            0 => emulator.set_pc(value),
+            1 => emulator.set_accumulator(value),
+            2 => emulator.set_flags(byte_flags(value)),
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn write_registers(&mut self, arguments: &str) -> Option<String> {
+        let [pc, accumulator, flags] = decode_hex(arguments)?.try_into().ok()?;
+
+        self.set_register(0, pc)?;
+        self.set_register(1, accumulator)?;
+        self.set_register(2, flags)?;
+        Some("OK".to_owned())
+    }
+
+    fn write_register(&mut self, arguments: &str) -> Option<String> {
+        let (register, value) = arguments.split_once('=')?;
+        let [value] = decode_hex(value)?.try_into().ok()?;
+
+        self.set_register(parse_hex(register)?, value)?;
+        Some("OK".to_owned())
+    }
+
+    /// Byte at a GDB address, RAM first then the ROM words big endian
+    fn memory_byte(&self, address: usize) -> Option<u8> {
+        let emulator = self.debugger.emulator();
+
+        if address < RAM_SIZE {
+            return Some(emulator.read_ram(u8::try_from(address).ok()?));
+        }
+
+        let offset = address.checked_sub(GDB_ROM_START)?;
+        let word = emulator.rom().get(offset / 2)?.to_be_bytes();
+        Some(word[offset % 2])
+    }
+
+    /// `addr,length`
+    fn read_memory(&self, arguments: &str) -> Option<String> {
+        let (address, length) = arguments.split_once(',')?;
+        let address = parse_hex(address)?;
+        let length = parse_hex(length)?.min(RAM_SIZE + ROM_SIZE * 2);
+
+        let bytes = (address..address.checked_add(length)?)
+            .map(|address| self.memory_byte(address))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(encode_hex(&bytes))
+    }
+
+    /// `addr,length:bytes`, in RAM only
+    fn write_memory(&mut self, arguments: &str) -> Option<String> {
+        let (range, bytes) = arguments.split_once(':')?;
+        let (address, length) = range.split_once(',')?;
+        let address = parse_hex(address)?;
+        let bytes = decode_hex(bytes)?;
+
+        if parse_hex(length)? != bytes.len() {
+            return None;
+        }
+
+        address
+            .checked_add(bytes.len())
+            .filter(|end| *end <= RAM_SIZE)?;
+
+        for (address, value) in (address..).zip(bytes) {
+            self.debugger
+                .emulator_mut()
+                .write_ram(u8::try_from(address).ok()?, value);
+        }
+
+        Some("OK".to_owned())
+    }
+
+    /// `s` and `c` can give the address to resume from
+    fn resume(
+        &mut self,
+        arguments: &str,
+        execute: impl FnOnce(&mut Self) -> String,
+    ) -> Option<String> {
+        if !arguments.is_empty() {
+            let pc = u8::try_from(parse_hex(arguments)?).ok()?;
+            self.debugger.emulator_mut().set_pc(pc);
+        }
+
+        Some(execute(self))
+    }
+
+    fn continue_until_stop(&mut self, mut interrupted: impl FnMut() -> bool) -> String {
+        loop {
+            if let Err(stop) = self.debugger.run(CONTINUE_CHUNK) {
+                return stop_reply(signal(stop));
+            }
+
+            if interrupted() {
+                return stop_reply(SIGINT);
+            }
+
+            // `run` doesn't stop on a breakpoint at the first instruction it executes
+            if self
+                .debugger
+                .breakpoints()
+                .contains(&self.debugger.emulator().pc())
+            {
+                return stop_reply(SIGTRAP);
+            }
+        }
+    }
+
+    /// `type,addr,kind`, software and hardware breakpoints are the same, watchpoints aren't supported
+    fn breakpoint(&mut self, insert: bool, arguments: &str) -> Option<String> {
+        let mut fields = arguments.split(',');
+        let kind = fields.next()?;
+
+        if !matches!(kind, "0" | "1") {
+            return Some(String::new());
+        }
+
+        let address = u8::try_from(parse_hex(fields.next()?)?).ok()?;
+        let breakpoints = self.debugger.breakpoints_mut();
+
+        if insert {
+            breakpoints.insert(address);
+        } else {
+            breakpoints.remove(&address);
+        }
+
+        Some("OK".to_owned())
+    }
+
+    /// General queries and settings, `q`, `Q`, `H` and `v` packets
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+".to_owned();
+        }
+
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            return Self::target_xml(range).unwrap_or_else(|| ERROR.to_owned());
+        }
+
+        match packet {
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK".to_owned()
+            }
+            "qAttached" => "1".to_owned(),
+            _ if packet.starts_with('H') => "OK".to_owned(),
+            _ => String::new(),
+        }
+    }
+
+    /// `offset,length` of the target description, `m` before a part and `l` before the last one
+    fn target_xml(range: &str) -> Option<String> {
+        let (offset, length) = range.split_once(',')?;
+        let offset = parse_hex(offset)?.min(TARGET_XML.len());
+        let end = offset
+            .saturating_add(parse_hex(length)?)
+            .min(TARGET_XML.len());
+        let part = TARGET_XML.get(offset..end)?;
+
+        Some(if end == TARGET_XML.len() {
+            format!("l{part}")
+        } else {
+            format!("m{part}")
+        })
+    }
+
+    /// Answer the packets of a connection until GDB detaches, kills the program or disconnects
+    pub fn serve(&mut self, stream: &mut TcpStream) -> io::Result<()> {
+        // Packets are small and each one waits for the previous reply
+        stream.set_nodelay(true)?;
+
+        let mut pending = VecDeque::new();
+
+        while !self.finished {
+            let Some(incoming) = read_incoming(stream, &mut pending)? else {
+                return Ok(());
+            };
+
+            match incoming {
+                Incoming::Interrupt => stream.write_all(&frame(&stop_reply(SIGINT)))?,
+                Incoming::Packet { valid: false, .. } if !self.no_ack => stream.write_all(b"-")?,
+                Incoming::Packet { valid: false, .. } => {}
+                Incoming::Packet { payload, .. } => {
+                    if !self.no_ack {
+                        stream.write_all(b"+")?;
+                    }
+
+                    let reply = self.answer(&payload, || interrupt_pending(stream, &mut pending));
+                    stream.write_all(&frame(&reply))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
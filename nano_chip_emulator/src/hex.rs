@@ -0,0 +1,25 @@
+use std::fmt::Write;
+
+/// Bytes as pairs of uppercase hexadecimal digits, like `2A00FF`
+pub fn encode_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        let _ = write!(hex, "{byte:02X}");
+    }
+
+    hex
+}
+
+/// Read pairs of hexadecimal digits in either case, `None` for an odd length or another character
+pub fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` alone would accept a sign like in `+F`
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,48 @@
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![allow(clippy::struct_excessive_bools)]
+#![allow(clippy::too_many_lines)]
+#![allow(clippy::must_use_candidate)]
+#![allow(clippy::missing_errors_doc)]
+
+mod bus;
+mod coverage;
+mod debugger;
+mod devices;
+mod gdb;
+mod hex;
+mod loader;
+mod memory_map;
+mod nano_chip_emulator;
+mod profile;
+mod snapshot;
+mod test_spec;
+mod tests;
+mod trace;
+mod tui;
+mod value;
+mod vcd;
+mod watch;
+
+pub use bus::{Bus, BusError, Device, Mapping};
+pub use coverage::Coverage;
+pub use debugger::{Debugger, DebuggerError, Reply, Stop, CONTINUE_LIMIT, HISTORY_LIMIT};
+pub use devices::{Console, Leds, SevenSegment, Switches};
+pub use gdb::{GdbStub, GDB_ROM_START, TARGET_XML};
+pub use loader::{load_program, LoadError, LoadedProgram};
+pub use memory_map::{load_memory_map, MemoryMapError};
+pub use nano_chip_emulator::{
+    HaltPolicy, IllegalOpcodePolicy, NanoChipEmulator, PcWrapPolicy, RamWrite, RomError,
+    RunOutcome, StepRecord, StepResult, Trap, TrapPolicies,
+};
+pub use nano_chip_isa::Flags;
+pub use profile::Profile;
+pub use snapshot::{DeviceState, Snapshot, SnapshotError, SNAPSHOT_VERSION};
+pub use test_spec::{
+    CaseResult, Mismatch, SpecError, Target, TestCase, TestSpec, DEFAULT_TEST_CYCLES,
+};
+pub use trace::{TraceFormat, TraceWriter};
+pub use tui::{Key, Tui, DEFAULT_SPEED, MAX_SPEED};
+pub use value::{parse_value, Symbols, ValueError};
+pub use vcd::{VcdConfig, VcdWriter, MIN_CLOCK_PERIOD};
+pub use watch::{Condition, ConditionError, WatchKind, Watchpoint, Watchpoints};
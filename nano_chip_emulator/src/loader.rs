@@ -0,0 +1,208 @@
+use std::fmt;
+use std::path::Path;
+
+use nano_chip_assembler::{AssembleError, DebugInfo, DebugInfoError};
+
+use crate::value::Symbols;
+use crate::{NanoChipEmulator, RomError};
+
+/// A program ready to run, with the symbols and test annotations of its source if it was loaded from
+/// source, or the symbols and source lines of its debug info
+pub struct LoadedProgram {
+    pub emulator: NanoChipEmulator,
+    pub symbols: Symbols,
+    /// Line and text of each `;@test` annotation, in the same form as `TestSpec::cases`
+    pub tests: Vec<(usize, String)>,
+    /// Line and text each ROM word was assembled from, by address, empty for binary images without
+    /// debug info
+    ///
+    /// The text is empty when the debug info points to a source file that can't be read
+    pub source_lines: Vec<(usize, String)>,
+    /// The `.asm` file, or the source named by the debug info
+    pub source_path: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Read(std::io::Error),
+    Assemble {
+        file_name: String,
+        source: String,
+        errors: Vec<AssembleError>,
+    },
+    Rom(RomError),
+    DebugInfo {
+        file_name: String,
+        error: DebugInfoError,
+    },
+    /// The debug info was written for another program, holds the word counts and ROM hashes
+    DebugInfoMismatch {
+        file_name: String,
+        expected: (usize, u64),
+        found: (usize, u64),
+    },
+}
+
+impl LoadError {
+    /// Error message, assembly errors are shown like the assembler does
+    pub fn render(&self, color: bool) -> String {
+        match self {
+            Self::Read(read_error) => format!("can't read input file : {read_error}"),
+            Self::Assemble {
+                file_name,
+                source,
+                errors,
+            } => errors
+                .iter()
+                .map(|error| error.render(file_name, source, color))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Self::Rom(rom_error) => rom_error.to_string(),
+            Self::DebugInfo { file_name, error } => format!("{file_name} {error}"),
+            Self::DebugInfoMismatch {
+                file_name,
+                expected,
+                found,
+            } => format!(
+                "{file_name} was written for another program, {} words with hash {:016X} instead of {} words with hash {:016X}, assemble it again with --debug-info",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Debug info written by `nano_chip_assembler --debug-info` next to a binary, `program.dbg` for
+/// `program.o`, `None` when there is no such file
+///
+/// It is rejected when it wasn't written for the `words` words loaded in `emulator`, like after the
+/// binary was assembled again without it
+fn load_debug_info(
+    path: &Path,
+    emulator: &NanoChipEmulator,
+    words: usize,
+) -> Result<Option<DebugInfo>, LoadError> {
+    let debug_path = path.with_extension("dbg");
+
+    let text = match std::fs::read_to_string(&debug_path) {
+        Ok(text) => text,
+        Err(read_error) if read_error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(read_error) => return Err(LoadError::Read(read_error)),
+    };
+
+    let debug_info = DebugInfo::parse(&text).map_err(|error| LoadError::DebugInfo {
+        file_name: debug_path.display().to_string(),
+        error,
+    })?;
+
+    let expected = (words, emulator.rom_hash());
+    let found = (debug_info.words, debug_info.rom_hash);
+
+    if found != expected {
+        return Err(LoadError::DebugInfoMismatch {
+            file_name: debug_path.display().to_string(),
+            expected,
+            found,
+        });
+    }
+
+    Ok(Some(debug_info))
+}
+
+/// Load a binary ROM image, or assemble a `.asm` source so that its symbols can be used
+///
+/// The symbols and source lines of a binary come from its debug info file, see `load_debug_info`
+pub fn load_program(path: &Path) -> Result<LoadedProgram, LoadError> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("asm"))
+    {
+        let source = std::fs::read_to_string(path).map_err(LoadError::Read)?;
+
+        let program =
+            nano_chip_assembler::assemble(&source).map_err(|errors| LoadError::Assemble {
+                file_name: path.display().to_string(),
+                source: source.clone(),
+                errors,
+            })?;
+
+        let emulator = NanoChipEmulator::from_bytes(&program.to_bytes()).map_err(LoadError::Rom)?;
+
+        let symbols = program
+            .symbols()
+            .iter()
+            .map(|symbol| (symbol.prefixed_name(), symbol.value))
+            .collect();
+
+        let tests = program
+            .tests()
+            .iter()
+            .map(|test| (test.line, test.text.clone()))
+            .collect();
+
+        let source_lines = program
+            .source_lines()
+            .iter()
+            .map(|source_line| (source_line.line, source_line.text.clone()))
+            .collect();
+
+        Ok(LoadedProgram {
+            emulator,
+            symbols,
+            tests,
+            source_lines,
+            source_path: Some(path.display().to_string()),
+        })
+    } else {
+        let bytes = std::fs::read(path).map_err(LoadError::Read)?;
+        let emulator = NanoChipEmulator::from_bytes(&bytes).map_err(LoadError::Rom)?;
+
+        let Some(debug_info) = load_debug_info(path, &emulator, bytes.len() / 2)? else {
+            return Ok(LoadedProgram {
+                emulator,
+                symbols: Symbols::new(),
+                tests: Vec::new(),
+                source_lines: Vec::new(),
+                source_path: None,
+            });
+        };
+
+        // A relative source path is relative to the debug info file
+        let source_path = path.parent().map_or_else(
+            || debug_info.source.clone().into(),
+            |parent| parent.join(&debug_info.source),
+        );
+        let source = std::fs::read_to_string(&source_path).unwrap_or_default();
+        let source = source.lines().collect::<Vec<_>>();
+
+        Ok(LoadedProgram {
+            emulator,
+            symbols: debug_info
+                .symbols
+                .iter()
+                .map(|symbol| (symbol.prefixed_name(), symbol.value))
+                .collect(),
+            tests: Vec::new(),
+            source_lines: debug_info
+                .lines
+                .iter()
+                .map(|&line| {
+                    let text = line
+                        .checked_sub(1)
+                        .and_then(|index| source.get(index))
+                        .copied()
+                        .unwrap_or_default();
+                    (line, text.to_owned())
+                })
+                .collect(),
+            source_path: Some(source_path.display().to_string()),
+        })
+    }
+}
@@ -1,44 +1,686 @@
-#![feature(bigint_helper_methods)]
 #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
-#![allow(clippy::struct_excessive_bools)]
-#![allow(clippy::too_many_lines)]
 
-use std::io;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, ExitCode, Stdio};
+use std::time::{Duration, Instant};
 
-use crate::nano_chip_emulator::NanoChipEmulator;
+use nano_chip_emulator::{
+    load_memory_map, load_program, parse_value, Coverage, Debugger, GdbStub, IllegalOpcodePolicy,
+    Key, LoadedProgram, NanoChipEmulator, PcWrapPolicy, Profile, Reply, RunOutcome, Snapshot,
+    Symbols, TraceFormat, TraceWriter, TrapPolicies, Tui, VcdConfig, VcdWriter, MIN_CLOCK_PERIOD,
+};
+use nano_chip_isa::{RAM_SIZE, ROM_SIZE};
 
-mod nano_chip_emulator;
+const USAGE: &str = "\
+Usage : nano_chip_emulator program.o|program.asm [--memory-map file] [--load-state file]
+        nano_chip_emulator run program.o|program.asm [--memory-map file] [--load-state file] [--save-state file]
+            [--max-cycles N] [--illegal-opcode stop|nop] [--pc-wrap stop|wrap]
+            [--trace file] [--trace-format text|csv|jsonl]
+            [--vcd file] [--vcd-period ns] [--vcd-ram addr[=name]]... [--vcd-name signal=name]...
+            [--coverage file] [--lcov file] [--profile file] [--profile-folded file]
+        nano_chip_emulator tui program.o|program.asm [--memory-map file] [--load-state file]
+        nano_chip_emulator gdb program.o|program.asm [--memory-map file] [--load-state file] [--port N]";
 
-fn main() {
-    if let Some(rom_file) = std::env::args().nth(1) {
-        match std::fs::read(rom_file) {
-            Ok(input_bytes) => {
-                if input_bytes.len() <= 512 {
-                    let mut rom = [0u16; 256];
+/// Cycle budget of the run mode when none is given
+const DEFAULT_MAX_CYCLES: u64 = 1_000_000;
 
-                    for i in 0..input_bytes.len() / 2 {
-                        rom[i] = (input_bytes[i * 2] as u16) << 8 | input_bytes[i * 2 + 1] as u16
-                    }
+/// Port the GDB stub listens on when none is given
+const DEFAULT_GDB_PORT: u16 = 1234;
 
-                    let mut emulator = NanoChipEmulator::new(&rom);
+/// Exit codes of the run mode
+const EXIT_HALTED: u8 = 0;
+const EXIT_ERROR: u8 = 1;
+const EXIT_TIMEOUT: u8 = 2;
+const EXIT_TRAP: u8 = 3;
 
-                    let stdin = io::stdin();
+/// Load a program, map the devices of the memory map file and restore a snapshot, assembly errors
+/// are shown like the assembler does
+fn load(
+    file_name: &str,
+    memory_map: Option<&str>,
+    load_state: Option<&str>,
+) -> Result<LoadedProgram, String> {
+    let mut program = load_program(Path::new(file_name)).map_err(|load_error| {
+        format!("Error, {}", load_error.render(io::stdout().is_terminal()))
+    })?;
 
-                    loop {
-                        emulator.print_status();
-                        emulator.tick();
-                        stdin.read_line(&mut String::new()).unwrap();
-                    }
-                } else {
-                    println!("Error rom file size must 512 bytes");
+    if let Some(memory_map) = memory_map {
+        let text = std::fs::read_to_string(memory_map)
+            .map_err(|read_error| format!("Error, can't read {memory_map} : {read_error}"))?;
+
+        load_memory_map(&text, &program.symbols, program.emulator.bus_mut())
+            .map_err(|map_error| format!("Error, {memory_map} {map_error}"))?;
+    }
+
+    if let Some(load_state) = load_state {
+        Snapshot::load(Path::new(load_state))
+            .and_then(|snapshot| program.emulator.restore(&snapshot))
+            .map_err(|snapshot_error| format!("Error, {load_state} {snapshot_error}"))?;
+    }
+
+    Ok(program)
+}
+
+/// Options of the debugger and the TUI
+struct DebugOptions {
+    file_name: String,
+    memory_map: Option<String>,
+    load_state: Option<String>,
+}
+
+fn parse_debug_options(arguments: &[String]) -> Result<DebugOptions, String> {
+    let mut file_name = None;
+    let mut memory_map = None;
+    let mut load_state = None;
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        let mut option_value = || {
+            arguments
+                .next()
+                .cloned()
+                .ok_or_else(|| format!("Error, {argument} needs a value"))
+        };
+
+        match argument.as_str() {
+            "--memory-map" => memory_map = Some(option_value()?),
+            "--load-state" => load_state = Some(option_value()?),
+            _ if argument.starts_with("--") => {
+                return Err(format!("Error, unknown option {argument}"))
+            }
+            _ if file_name.is_none() => file_name = Some(argument.clone()),
+            _ => return Err(format!("Error, unexpected argument {argument}")),
+        }
+    }
+
+    Ok(DebugOptions {
+        file_name: file_name.ok_or("Error, a file is needed as parameter")?,
+        memory_map,
+        load_state,
+    })
+}
+
+/// Options of the run mode
+struct RunOptions {
+    file_name: String,
+    memory_map: Option<String>,
+    load_state: Option<String>,
+    save_state: Option<String>,
+    max_cycles: u64,
+    trap_policies: TrapPolicies,
+    trace_file: Option<String>,
+    trace_format: TraceFormat,
+    vcd_file: Option<String>,
+    vcd_config: VcdConfig,
+    /// Arguments of `--vcd-ram`, resolved once the program symbols are known
+    vcd_ram_cells: Vec<String>,
+    coverage_file: Option<String>,
+    lcov_file: Option<String>,
+    profile_file: Option<String>,
+    /// Folded stacks for flamegraph tools
+    profile_folded_file: Option<String>,
+}
+
+fn parse_run_options(arguments: &[String]) -> Result<RunOptions, String> {
+    let mut file_name = None;
+    let mut options = RunOptions {
+        file_name: String::new(),
+        memory_map: None,
+        load_state: None,
+        save_state: None,
+        max_cycles: DEFAULT_MAX_CYCLES,
+        trap_policies: TrapPolicies::STOP,
+        trace_file: None,
+        trace_format: TraceFormat::Text,
+        vcd_file: None,
+        vcd_config: VcdConfig::default(),
+        vcd_ram_cells: Vec::new(),
+        coverage_file: None,
+        lcov_file: None,
+        profile_file: None,
+        profile_folded_file: None,
+    };
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        let mut option_value = || {
+            arguments
+                .next()
+                .ok_or_else(|| format!("Error, {argument} needs a value"))
+        };
+
+        match argument.as_str() {
+            "--max-cycles" => {
+                let value = option_value()?;
+                options.max_cycles = value
+                    .parse()
+                    .map_err(|_| format!("Error, invalid cycle count {value}"))?;
+            }
+            "--illegal-opcode" => {
+                options.trap_policies.illegal_opcode = match option_value()?.as_str() {
+                    "stop" => IllegalOpcodePolicy::Stop,
+                    "nop" => IllegalOpcodePolicy::Nop,
+                    other => return Err(format!("Error, invalid illegal opcode policy {other}")),
+                };
+            }
+            "--pc-wrap" => {
+                options.trap_policies.pc_wrap = match option_value()?.as_str() {
+                    "stop" => PcWrapPolicy::Stop,
+                    "wrap" => PcWrapPolicy::Wrap,
+                    other => return Err(format!("Error, invalid pc wrap policy {other}")),
+                };
+            }
+            "--memory-map" => options.memory_map = Some(option_value()?.clone()),
+            "--load-state" => options.load_state = Some(option_value()?.clone()),
+            "--save-state" => options.save_state = Some(option_value()?.clone()),
+            "--trace" => options.trace_file = Some(option_value()?.clone()),
+            "--trace-format" => {
+                let value = option_value()?;
+                options.trace_format = TraceFormat::from_name(value)
+                    .ok_or_else(|| format!("Error, invalid trace format {value}"))?;
+            }
+            "--vcd" => options.vcd_file = Some(option_value()?.clone()),
+            "--vcd-period" => {
+                let value = option_value()?;
+                options.vcd_config.clock_period =
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&period| period >= MIN_CLOCK_PERIOD)
+                        .ok_or_else(|| {
+                            format!("Error, invalid clock period {value}, it must be at least {MIN_CLOCK_PERIOD} ns")
+                        })?;
+            }
+            "--vcd-ram" => options.vcd_ram_cells.push(option_value()?.clone()),
+            "--coverage" => options.coverage_file = Some(option_value()?.clone()),
+            "--lcov" => options.lcov_file = Some(option_value()?.clone()),
+            "--profile" => options.profile_file = Some(option_value()?.clone()),
+            "--profile-folded" => options.profile_folded_file = Some(option_value()?.clone()),
+            "--vcd-name" => {
+                let value = option_value()?;
+                let renamed = value
+                    .split_once('=')
+                    .is_some_and(|(signal, name)| options.vcd_config.rename(signal, name));
+
+                if !renamed {
+                    return Err(format!(
+                        "Error, invalid signal name {value}, expected signal=name"
+                    ));
                 }
             }
-            Err(read_error) => {
-                println!("Error can't read input file : {read_error}");
+            _ if argument.starts_with("--") => {
+                return Err(format!("Error, unknown option {argument}"))
             }
+            _ if file_name.is_none() => file_name = Some(argument.clone()),
+            _ => return Err(format!("Error, unexpected argument {argument}")),
+        }
+    }
+
+    options.file_name = file_name.ok_or("Error, a file is needed as parameter")?;
+
+    Ok(options)
+}
+
+fn create_file(file_name: &str) -> Result<BufWriter<File>, String> {
+    File::create(file_name)
+        .map(BufWriter::new)
+        .map_err(|create_error| format!("Error, can't create {file_name} : {create_error}"))
+}
+
+/// Write the coverage report and lcov file asked for in the options, `source_path` is the file the
+/// lcov lines refer to
+fn write_coverage(
+    options: &RunOptions,
+    coverage: &Coverage,
+    rom: &[u16; ROM_SIZE],
+    source_lines: &[(usize, String)],
+    symbols: &Symbols,
+    source_path: &str,
+) -> Result<(), String> {
+    if let Some(coverage_file) = &options.coverage_file {
+        let report = format!(
+            "{}\n{}",
+            coverage.listing(rom, source_lines, symbols),
+            coverage.summary(rom, source_lines, symbols)
+        );
+
+        std::fs::write(coverage_file, report)
+            .map_err(|write_error| format!("Error, can't write {coverage_file} : {write_error}"))?;
+    }
+
+    if let Some(lcov_file) = &options.lcov_file {
+        let source_path = std::fs::canonicalize(source_path).map_or_else(
+            |_| source_path.to_owned(),
+            |path| path.display().to_string(),
+        );
+        let lcov = coverage.lcov(rom, source_lines, symbols, &source_path);
+
+        std::fs::write(lcov_file, lcov)
+            .map_err(|write_error| format!("Error, can't write {lcov_file} : {write_error}"))?;
+    }
+
+    Ok(())
+}
+
+/// Write the profile report and folded stacks asked for in the options
+fn write_profile(
+    options: &RunOptions,
+    profile: &Profile,
+    rom: &[u16; ROM_SIZE],
+    symbols: &Symbols,
+) -> Result<(), String> {
+    if let Some(profile_file) = &options.profile_file {
+        std::fs::write(profile_file, profile.report(rom, symbols))
+            .map_err(|write_error| format!("Error, can't write {profile_file} : {write_error}"))?;
+    }
+
+    if let Some(folded_file) = &options.profile_folded_file {
+        std::fs::write(folded_file, profile.folded(rom, symbols))
+            .map_err(|write_error| format!("Error, can't write {folded_file} : {write_error}"))?;
+    }
+
+    Ok(())
+}
+
+/// Tell how a run ended and give its exit code
+fn print_outcome(outcome: RunOutcome, max_cycles: u64) -> u8 {
+    match outcome {
+        RunOutcome::Halted { pc } => {
+            println!("Halted at 0x{pc:02X}");
+            EXIT_HALTED
+        }
+        RunOutcome::Timeout => {
+            println!("Timeout, still running after {max_cycles} cycles");
+            EXIT_TIMEOUT
+        }
+        RunOutcome::Trap(trap) => {
+            println!("Trap, {trap}");
+            EXIT_TRAP
+        }
+    }
+}
+
+/// Registers, RAM and devices at the end of a run
+fn print_state(emulator: &NanoChipEmulator) {
+    let accumulator = emulator.accumulator();
+
+    println!("Accumulator : 0x{accumulator:02X} ({accumulator})");
+    println!("Flags : {}", emulator.flags().letters());
+    println!("Program Counter : 0x{:02X}", emulator.pc());
+    println!("Cycles : {}", emulator.cycles());
+    println!("RAM :");
+    println!("{}", emulator.ram_dump(0, RAM_SIZE));
+
+    if !emulator.bus().mappings().is_empty() {
+        println!("Devices :");
+        println!("{}", emulator.bus().device_states());
+    }
+}
+
+/// Run a program without interaction, the exit code tells how it ended
+fn run(mut options: RunOptions) -> Result<ExitCode, String> {
+    let max_cycles = options.max_cycles;
+
+    let program = load(
+        &options.file_name,
+        options.memory_map.as_deref(),
+        options.load_state.as_deref(),
+    )?;
+    let mut emulator = program.emulator;
+    emulator.set_trap_policies(options.trap_policies);
+
+    for cell in &options.vcd_ram_cells {
+        let (address, name) = cell
+            .split_once('=')
+            .map_or((cell.as_str(), None), |(address, name)| {
+                (address, Some(name))
+            });
+        let address = parse_value(address, &program.symbols)
+            .map_err(|value_error| format!("Error, --vcd-ram {value_error}"))?;
+
+        options.vcd_config.add_ram_cell(address, name);
+    }
+
+    let mut trace = match &options.trace_file {
+        Some(trace_file) => Some(
+            TraceWriter::new(options.trace_format, create_file(trace_file)?).map_err(
+                |write_error| format!("Error, can't write {trace_file} : {write_error}"),
+            )?,
+        ),
+        None => None,
+    };
+
+    if let Some(trace) = &mut trace {
+        trace.set_symbols(&program.symbols);
+    }
+
+    let mut vcd = match &options.vcd_file {
+        Some(vcd_file) => Some(
+            VcdWriter::new(
+                std::mem::take(&mut options.vcd_config),
+                create_file(vcd_file)?,
+                &emulator,
+            )
+            .map_err(|write_error| format!("Error, can't write {vcd_file} : {write_error}"))?,
+        ),
+        None => None,
+    };
+
+    if options.lcov_file.is_some() && program.source_lines.is_empty() {
+        return Err("Error, --lcov needs an assembly source or debug info".to_owned());
+    }
+
+    let mut coverage =
+        (options.coverage_file.is_some() || options.lcov_file.is_some()).then(Coverage::new);
+    let mut profile = (options.profile_file.is_some() || options.profile_folded_file.is_some())
+        .then(Profile::new);
+
+    // Outputs stop at the first write error, which is reported once the run is over
+    let mut trace_result = Ok(());
+    let mut vcd_result = Ok(());
+
+    let outcome = emulator.run_to_halt_with(max_cycles, |step| {
+        if let Some(coverage) = &mut coverage {
+            coverage.record(step);
         }
-    } else {
-        println!("Error, a file is needed as parameter");
+
+        if let Some(profile) = &mut profile {
+            profile.record(step);
+        }
+
+        if let (Some(trace), Ok(())) = (&mut trace, &trace_result) {
+            trace_result = trace.write(step);
+        }
+
+        if let (Some(vcd), Ok(())) = (&mut vcd, &vcd_result) {
+            vcd_result = vcd.write(step);
+        }
+    });
+
+    if let (Some(trace_file), Some(mut trace)) = (&options.trace_file, trace) {
+        trace_result
+            .and_then(|()| trace.flush())
+            .map_err(|write_error| format!("Error, can't write {trace_file} : {write_error}"))?;
+    }
+
+    if let (Some(vcd_file), Some(vcd)) = (&options.vcd_file, vcd) {
+        vcd_result
+            .and_then(|()| vcd.finish().map(drop))
+            .map_err(|write_error| format!("Error, can't write {vcd_file} : {write_error}"))?;
+    }
+
+    if let Some(coverage) = &coverage {
+        write_coverage(
+            &options,
+            coverage,
+            emulator.rom(),
+            &program.source_lines,
+            &program.symbols,
+            program.source_path.as_deref().unwrap_or(&options.file_name),
+        )?;
     }
+
+    if let Some(profile) = &profile {
+        write_profile(&options, profile, emulator.rom(), &program.symbols)?;
+    }
+
+    if let Some(save_state) = &options.save_state {
+        emulator
+            .snapshot()
+            .save(Path::new(save_state))
+            .map_err(|write_error| format!("Error, can't write {save_state} : {write_error}"))?;
+    }
+
+    let exit_code = print_outcome(outcome, max_cycles);
+    print_state(&emulator);
+
+    Ok(ExitCode::from(exit_code))
+}
+
+/// Debugger knowing the symbols and source lines of the program
+fn debugger(program: LoadedProgram) -> Debugger {
+    let mut debugger = Debugger::new(program.emulator);
+    debugger.add_symbols(&program.symbols);
+
+    if let Some(source_path) = &program.source_path {
+        let file_name = Path::new(source_path).file_name().map_or_else(
+            || source_path.clone(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let lines = program.source_lines.iter().map(|(line, _)| *line).collect();
+
+        debugger.set_source(&file_name, lines);
+    }
+
+    debugger
+}
+
+/// Interactive debugger prompt
+fn debug(options: &DebugOptions) -> Result<ExitCode, String> {
+    let program = load(
+        &options.file_name,
+        options.memory_map.as_deref(),
+        options.load_state.as_deref(),
+    )?;
+    let mut debugger = debugger(program);
+
+    println!("Type `help` for the list of commands");
+    println!("{}", debugger.location());
+
+    let mut line = String::new();
+
+    loop {
+        print!("(nano) ");
+        let _ = io::stdout().flush();
+
+        line.clear();
+
+        // Stop at the end of the input
+        if !matches!(io::stdin().read_line(&mut line), Ok(read) if read > 0) {
+            println!();
+            break;
+        }
+
+        match debugger.execute(&line) {
+            Ok(Reply::Output(output)) => {
+                if !output.is_empty() {
+                    println!("{output}");
+                }
+            }
+            Ok(Reply::Quit) => break,
+            Err(debugger_error) => println!("Error, {debugger_error}"),
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Time between two frames of the TUI
+const FRAME: Duration = Duration::from_millis(30);
+
+/// Run `stty` on the terminal of the standard input and return what it printed
+fn stty(arguments: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(arguments)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|stty_error| format!("Error, the TUI needs stty : {stty_error}"))?;
+
+    if !output.status.success() {
+        return Err("Error, the TUI needs a terminal".to_owned());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Terminal without line buffering nor echo, showing the alternate screen, restored when dropped
+struct RawTerminal {
+    /// Settings to restore, as printed by `stty -g`
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enable() -> Result<Self, String> {
+        let saved = stty(&["-g"])?;
+
+        // Reads return at once, with nothing when no key was pressed
+        stty(&["-icanon", "-echo", "min", "0", "time", "0"])?;
+
+        print!("\x1b[?1049h\x1b[?25l");
+        let _ = io::stdout().flush();
+
+        Ok(Self { saved })
+    }
+
+    /// Number of lines of the terminal, 24 when unknown
+    fn rows() -> usize {
+        stty(&["size"])
+            .ok()
+            .and_then(|size| size.split_whitespace().next()?.parse().ok())
+            .unwrap_or(24)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Full screen front end of the debugger
+fn tui(options: &DebugOptions) -> Result<ExitCode, String> {
+    let program = load(
+        &options.file_name,
+        options.memory_map.as_deref(),
+        options.load_state.as_deref(),
+    )?;
+    let debugger = debugger(program);
+
+    let mut tui = Tui::new(debugger, &options.file_name);
+
+    let _terminal = RawTerminal::enable()?;
+    let mut stdin = io::stdin();
+    let mut buffer = [0; 64];
+    let rows = RawTerminal::rows();
+    let mut last_frame = Instant::now();
+
+    while !tui.quit_requested() {
+        let read = stdin.read(&mut buffer).unwrap_or(0);
+
+        for key in Key::parse(&buffer[..read]) {
+            tui.handle_key(key);
+        }
+
+        let now = Instant::now();
+        tui.tick(now - last_frame);
+        last_frame = now;
+
+        let frame = tui.render(rows);
+        print!("\x1b[H{}\x1b[J", frame.replace('\n', "\x1b[K\n"));
+        let _ = io::stdout().flush();
+
+        std::thread::sleep(FRAME);
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Options of the debugger and the port of the GDB stub
+fn parse_gdb_options(arguments: &[String]) -> Result<(DebugOptions, u16), String> {
+    let mut port = DEFAULT_GDB_PORT;
+    let mut debug_arguments = Vec::new();
+    let mut arguments = arguments.iter();
+
+    while let Some(argument) = arguments.next() {
+        if argument == "--port" {
+            port = arguments
+                .next()
+                .and_then(|value| value.parse().ok())
+                .ok_or("Error, --port needs a port number")?;
+        } else {
+            debug_arguments.push(argument.clone());
+        }
+    }
+
+    Ok((parse_debug_options(&debug_arguments)?, port))
+}
+
+/// Wait for GDB on the local host and serve one connection
+fn gdb(options: &DebugOptions, port: u16) -> Result<ExitCode, String> {
+    let program = load(
+        &options.file_name,
+        options.memory_map.as_deref(),
+        options.load_state.as_deref(),
+    )?;
+    let mut stub = GdbStub::new(debugger(program));
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|bind_error| format!("Error, can't listen on port {port} : {bind_error}"))?;
+
+    println!("Waiting for GDB on 127.0.0.1:{port}, connect with `target remote :{port}`");
+
+    let (mut stream, address) = listener
+        .accept()
+        .map_err(|accept_error| format!("Error, can't accept GDB : {accept_error}"))?;
+
+    println!("GDB connected from {address}");
+
+    stub.serve(&mut stream)
+        .map_err(|serve_error| format!("Error, connection to GDB lost : {serve_error}"))?;
+
+    println!("GDB disconnected");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// What the command line asks for, with its parsed options
+enum Mode {
+    Debug(DebugOptions),
+    Tui(DebugOptions),
+    Run(Box<RunOptions>),
+    Gdb(DebugOptions, u16),
+}
+
+fn main() -> ExitCode {
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let mode = match arguments.as_slice() {
+        [command, run_arguments @ ..] if command == "run" => {
+            parse_run_options(run_arguments).map(|options| Mode::Run(Box::new(options)))
+        }
+        [command, tui_arguments @ ..] if command == "tui" => {
+            parse_debug_options(tui_arguments).map(Mode::Tui)
+        }
+        [command, gdb_arguments @ ..] if command == "gdb" => {
+            parse_gdb_options(gdb_arguments).map(|(options, port)| Mode::Gdb(options, port))
+        }
+        _ => parse_debug_options(&arguments).map(Mode::Debug),
+    };
+
+    // The usage only helps with wrong arguments, errors go to stderr to keep them out of the output
+    let mode = match mode {
+        Ok(mode) => mode,
+        Err(message) => {
+            eprintln!("{message}");
+            eprintln!("{USAGE}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    let result = match mode {
+        Mode::Debug(options) => debug(&options),
+        Mode::Tui(options) => tui(&options),
+        Mode::Run(options) => run(*options),
+        Mode::Gdb(options, port) => gdb(&options, port),
+    };
+
+    result.unwrap_or_else(|message| {
+        eprintln!("{message}");
+        ExitCode::from(EXIT_ERROR)
+    })
 }
@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::bus::{Bus, Device};
+use crate::devices::{Console, Leds, SevenSegment, Switches};
+use crate::value::{parse_value, Symbols};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMapError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MemoryMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} : {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for MemoryMapError {}
+
+/// Build the device described by the words following its address
+fn device(kind: &str, options: &[&str], symbols: &Symbols) -> Result<Box<dyn Device>, String> {
+    let option = |default: u8| -> Result<u8, String> {
+        match options {
+            [] => Ok(default),
+            [value] => parse_value(value, symbols).map_err(|value_error| value_error.to_string()),
+            _ => Err(format!("too many parameters for {kind}")),
+        }
+    };
+    let no_option = || {
+        if options.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{kind} takes no parameter"))
+        }
+    };
+
+    match kind {
+        "leds" => no_option().map(|()| Box::new(Leds::new()) as Box<dyn Device>),
+        "switches" => option(0).map(|initial| Box::new(Switches::new(initial)) as Box<dyn Device>),
+        "seven_segment" => match option(1)? {
+            0 => Err("a 7-segment display needs at least one digit".to_owned()),
+            digits => Ok(Box::new(SevenSegment::new(usize::from(digits)))),
+        },
+        "console" => no_option().map(|()| Box::new(Console::new()) as Box<dyn Device>),
+        _ => Err(format!(
+            "unknown device `{kind}`, expected leds, switches, seven_segment or console"
+        )),
+    }
+}
+
+/// Map the devices described by a memory map file on `bus`
+///
+/// Each line gives a device, its first address, then its parameter if any :
+///
+/// ```text
+/// leds          0xF0
+/// switches      0xF1 0b1010 ; position at power on, 0 by default
+/// seven_segment 0xF2 4      ; number of digits, 1 by default
+/// console       $console    ; addresses can be symbols of the program
+/// ```
+pub fn load_memory_map(text: &str, symbols: &Symbols, bus: &mut Bus) -> Result<(), MemoryMapError> {
+    for (line_n, line) in (1..).zip(text.lines()) {
+        let code = line.split_once(';').map_or(line, |(code, _)| code);
+        let error = |message: String| MemoryMapError {
+            line: line_n,
+            message,
+        };
+
+        let words = code.split_whitespace().collect::<Vec<_>>();
+
+        let [kind, address, options @ ..] = words.as_slice() else {
+            if words.is_empty() {
+                continue;
+            }
+
+            return Err(error("expected a device and its address".to_owned()));
+        };
+
+        let address =
+            parse_value(address, symbols).map_err(|value_error| error(value_error.to_string()))?;
+        let device = device(kind, options, symbols).map_err(error)?;
+
+        bus.map(address, device)
+            .map_err(|bus_error| error(bus_error.to_string()))?;
+    }
+
+    Ok(())
+}
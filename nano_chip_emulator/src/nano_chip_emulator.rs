@@ -1,408 +1,714 @@
-pub struct NanoChipEmulator<'a> {
-    rom: &'a [u16; 256],
-    ram: [u8; 256],
-    accumulator: u8,
-    z_flag: bool,
-    c_flag: bool,
-    v_flag: bool,
-    n_flag: bool,
-    pc: u8,
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+
+use nano_chip_isa::{
+    AddressingMode, Flags, InstructionInfo, Mnemonic, RAM_SIZE, ROM_SIZE, WORD_MASK,
+};
+
+use crate::bus::Bus;
+use crate::snapshot::{DeviceState, Snapshot, SnapshotError};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RomError {
+    /// The ROM image is bigger than the ROM, holds the size of the image in bytes
+    TooLarge(usize),
+    /// The ROM image ends in the middle of a word, holds the size of the image in bytes
+    OddLength(usize),
 }
 
-impl NanoChipEmulator<'_> {
-    pub const fn new(rom: &[u16; 256]) -> NanoChipEmulator {
-        NanoChipEmulator {
-            rom,
-            ram: [0; 256],
-            accumulator: 0,
-            z_flag: false,
-            c_flag: false,
-            v_flag: false,
-            n_flag: false,
-            pc: 0,
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(size) => write!(
+                f,
+                "rom file size must be at most {} bytes, got {size} bytes",
+                ROM_SIZE * 2
+            ),
+            Self::OddLength(size) => write!(
+                f,
+                "rom file size must be a multiple of 2 bytes, got {size} bytes"
+            ),
         }
     }
+}
 
-    /// Execute a single instruction
-    pub fn tick(&mut self) {
-        let instruction = self.rom[self.pc as usize];
+impl std::error::Error for RomError {}
+
+/// Unusual event met while executing an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    /// The ROM word at `pc` doesn't hold a known opcode
+    IllegalOpcode { pc: u8, word: u16 },
+    /// The instruction at the last ROM address moved the program counter past the end of the ROM
+    PcWrap,
+    /// The instruction at `pc` branched to itself, nothing will change anymore
+    Halted { pc: u8 },
+}
 
-        let opcode = (instruction >> 8) as u8;
-        let operand = (instruction & 0xFF) as u8;
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IllegalOpcode { pc, word } => write!(
+                f,
+                "illegal opcode 0x{:02X} at 0x{pc:02X}",
+                (word & WORD_MASK) >> 8
+            ),
+            Self::PcWrap => write!(f, "program counter wrapped past the end of the ROM"),
+            Self::Halted { pc } => write!(f, "program halted at 0x{pc:02X}"),
+        }
+    }
+}
 
-        match opcode {
-            // ST
-            0x01 => {
-                self.ram[operand as usize] = self.accumulator;
+/// Outcome of `tick`, traps are only reported when their policy is to stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    Ok,
+    Trap(Trap),
+}
 
-                self.pc += 1;
-            }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IllegalOpcodePolicy {
+    /// The instruction isn't executed and the trap is reported
+    Stop,
+    /// The instruction does nothing, like on the real CPU
+    Nop,
+}
 
-            // LD const
-            0x02 => {
-                self.accumulator = operand;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcWrapPolicy {
+    /// The program counter wraps to 0 and the trap is reported
+    Stop,
+    /// The program counter wraps to 0, like on the real CPU
+    Wrap,
+}
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HaltPolicy {
+    /// The trap is reported
+    Stop,
+    /// The CPU keeps branching to the same address, like the real CPU
+    Loop,
+}
 
-                self.pc += 1;
-            }
+/// What `tick` does on each trap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrapPolicies {
+    pub illegal_opcode: IllegalOpcodePolicy,
+    pub pc_wrap: PcWrapPolicy,
+    pub halt: HaltPolicy,
+}
 
-            // LD address
-            0x03 => {
-                self.accumulator = self.ram[operand as usize];
+impl TrapPolicies {
+    /// Behave like the real CPU, no trap is ever reported
+    pub const HARDWARE: Self = Self {
+        illegal_opcode: IllegalOpcodePolicy::Nop,
+        pc_wrap: PcWrapPolicy::Wrap,
+        halt: HaltPolicy::Loop,
+    };
+
+    /// Report every trap
+    pub const STOP: Self = Self {
+        illegal_opcode: IllegalOpcodePolicy::Stop,
+        pc_wrap: PcWrapPolicy::Stop,
+        halt: HaltPolicy::Stop,
+    };
+}
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+/// How `run_to_halt` ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The program branched to itself, or entered a loop that changes nothing
+    Halted {
+        pc: u8,
+    },
+    /// The cycle budget was used up
+    Timeout,
+    Trap(Trap),
+}
 
-                self.pc += 1;
-            }
+/// RAM cell changed by an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RamWrite {
+    pub address: u8,
+    pub old_value: u8,
+    pub new_value: u8,
+}
 
-            // AND const
-            0x04 => {
-                self.accumulator &= operand;
+/// What an executed instruction did, kept by `tick` until the next instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepRecord {
+    /// Number of instructions executed before this one
+    pub cycle: u64,
+    pub pc: u8,
+    /// ROM word of the instruction
+    pub word: u16,
+    pub accumulator_before: u8,
+    pub accumulator_after: u8,
+    pub flags_before: Flags,
+    pub flags_after: Flags,
+    pub ram_write: Option<RamWrite>,
+}
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+impl Default for TrapPolicies {
+    fn default() -> Self {
+        Self::HARDWARE
+    }
+}
 
-                self.pc += 1;
-            }
+#[derive(Clone)]
+pub struct NanoChipEmulator {
+    rom: [u16; ROM_SIZE],
+    bus: Bus,
+    accumulator: u8,
+    flags: Flags,
+    pc: u8,
+    /// Number of executed instructions since the last reset
+    cycles: u64,
+    trap_policies: TrapPolicies,
+    last_step: Option<StepRecord>,
+    /// Records of the last executed instructions, the oldest first, to step back
+    history: VecDeque<StepRecord>,
+    /// Number of records kept in `history`, 0 disables it
+    history_limit: usize,
+}
 
-            // AND addr
-            0x05 => {
-                self.accumulator &= self.ram[operand as usize];
+impl NanoChipEmulator {
+    pub const fn new(rom: [u16; ROM_SIZE]) -> Self {
+        Self {
+            rom,
+            bus: Bus::new(),
+            accumulator: 0,
+            flags: Flags::NONE,
+            pc: 0,
+            cycles: 0,
+            trap_policies: TrapPolicies::HARDWARE,
+            last_step: None,
+            history: VecDeque::new(),
+            history_limit: 0,
+        }
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    /// Load a binary ROM image, each word is stored big endian on two bytes
+    ///
+    /// Images smaller than the ROM are padded with zeros, images ending in the middle of a word are
+    /// rejected
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RomError> {
+        if bytes.len() > ROM_SIZE * 2 {
+            return Err(RomError::TooLarge(bytes.len()));
+        }
 
-                self.pc += 1;
-            }
+        if !bytes.len().is_multiple_of(2) {
+            return Err(RomError::OddLength(bytes.len()));
+        }
 
-            // OR const
-            0x06 => {
-                self.accumulator |= operand;
+        let mut rom = [0u16; ROM_SIZE];
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        for (word, word_bytes) in rom.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_be_bytes([word_bytes[0], word_bytes[1]]);
+        }
 
-                self.pc += 1;
-            }
+        Ok(Self::new(rom))
+    }
 
-            // OR addr
-            0x07 => {
-                self.accumulator |= self.ram[operand as usize];
+    /// Put the CPU and devices back in their power on state, the ROM, memory map, trap policies and
+    /// history limit are kept but the history is cleared
+    pub fn reset(&mut self) {
+        let trap_policies = self.trap_policies;
+        let history_limit = self.history_limit;
+        let mut bus = std::mem::take(&mut self.bus);
+        bus.reset();
+
+        *self = Self::new(self.rom);
+        self.bus = bus;
+        self.trap_policies = trap_policies;
+        self.history_limit = history_limit;
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    pub const fn trap_policies(&self) -> TrapPolicies {
+        self.trap_policies
+    }
 
-                self.pc += 1;
-            }
+    pub const fn set_trap_policies(&mut self, trap_policies: TrapPolicies) {
+        self.trap_policies = trap_policies;
+    }
 
-            // XOR const
-            0x08 => {
-                self.accumulator ^= operand;
+    pub const fn rom(&self) -> &[u16; ROM_SIZE] {
+        &self.rom
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    /// Plain RAM, without the devices
+    pub const fn ram(&self) -> &[u8; RAM_SIZE] {
+        self.bus.ram()
+    }
 
-                self.pc += 1;
-            }
+    pub const fn bus(&self) -> &Bus {
+        &self.bus
+    }
 
-            // XOR addr
-            0x09 => {
-                self.accumulator ^= self.ram[operand as usize];
+    /// Gives access to the devices and to `Bus::map`
+    pub const fn bus_mut(&mut self) -> &mut Bus {
+        &mut self.bus
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    /// Value at `address` as seen by the CPU, from the RAM or a device
+    pub fn read_ram(&self, address: u8) -> u8 {
+        self.bus.read(address)
+    }
 
-                self.pc += 1;
-            }
+    pub fn write_ram(&mut self, address: u8, value: u8) {
+        self.bus.write(address, value);
+    }
 
-            // ROL acc
-            0x0A => {
-                let new_c_flag = self.accumulator > 0x7F;
+    pub const fn accumulator(&self) -> u8 {
+        self.accumulator
+    }
 
-                self.accumulator <<= 1;
-                self.accumulator |= if self.c_flag { 1 } else { 0 };
+    pub const fn set_accumulator(&mut self, value: u8) {
+        self.accumulator = value;
+    }
 
-                self.c_flag = new_c_flag;
+    pub const fn flags(&self) -> Flags {
+        self.flags
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    pub const fn set_flags(&mut self, flags: Flags) {
+        self.flags = flags;
+    }
 
-                self.pc += 1;
-            }
+    pub const fn pc(&self) -> u8 {
+        self.pc
+    }
 
-            // ROR acc
-            0x0B => {
-                let new_c_flag = self.accumulator % 2 == 1;
+    pub const fn set_pc(&mut self, pc: u8) {
+        self.pc = pc;
+    }
 
-                self.accumulator >>= 1;
-                self.accumulator |= if self.c_flag { 0b10000000 } else { 0 };
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
 
-                self.c_flag = new_c_flag;
+    /// Record of the last executed instruction, None after a reset or an illegal opcode that stopped
+    pub const fn last_step(&self) -> Option<&StepRecord> {
+        self.last_step.as_ref()
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    pub const fn history_limit(&self) -> usize {
+        self.history_limit
+    }
 
-                self.pc += 1;
-            }
+    /// Keep the records of the last `limit` executed instructions to step back, 0 (the default) disables it
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
 
-            // ADD const
-            0x0C => {
-                let a_sign = self.accumulator > 0x7F;
-                let b_sign = operand > 0x7F;
+        while self.history.len() > limit {
+            self.history.pop_front();
+        }
+    }
 
-                (self.accumulator, self.c_flag) = self.accumulator.overflowing_add(operand);
+    /// Records of the instructions that can be undone, the most recent last
+    pub const fn history(&self) -> &VecDeque<StepRecord> {
+        &self.history
+    }
 
-                self.v_flag = a_sign == b_sign && a_sign != (self.accumulator > 0x7F);
+    /// Undo the last executed instruction, returns its record or None when the history is empty
+    ///
+    /// The program counter, accumulator, flags, cycle count and RAM are restored,
+    /// the devices are left as they are
+    pub fn step_back(&mut self) -> Option<StepRecord> {
+        let step = self.history.pop_back()?;
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        self.pc = step.pc;
+        self.accumulator = step.accumulator_before;
+        self.flags = step.flags_before;
+        self.cycles = step.cycle;
 
-                self.pc += 1;
+        if let Some(write) = step.ram_write {
+            if self.bus.device_at(write.address).is_none() {
+                self.bus.write(write.address, write.old_value);
             }
+        }
 
-            // ADD addr
-            0x0D => {
-                let a_sign = self.accumulator > 0x7F;
-                let b_sign = self.ram[operand as usize] > 0x7F;
-
-                (self.accumulator, self.c_flag) =
-                    self.accumulator.overflowing_add(self.ram[operand as usize]);
-
-                self.v_flag = a_sign == b_sign && a_sign != (self.accumulator > 0x7F);
-
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        self.last_step = self.history.back().copied();
+        Some(step)
+    }
 
-                self.pc += 1;
-            }
+    /// FNV-1a hash of the ROM, identifies the program in snapshots and debug info
+    pub fn rom_hash(&self) -> u64 {
+        nano_chip_isa::rom_hash(&self.rom)
+    }
 
-            // ADC const
-            0x0E => {
-                let a_sign = self.accumulator > 0x7F;
-                let b_sign = operand > 0x7F;
+    /// Save the CPU, RAM and device states, the history and trap policies aren't part of it
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rom_hash: self.rom_hash(),
+            ram: *self.bus.ram(),
+            accumulator: self.accumulator,
+            flags: self.flags,
+            pc: self.pc,
+            cycles: self.cycles,
+            devices: self
+                .bus
+                .mappings()
+                .iter()
+                .map(|mapping| DeviceState {
+                    start: mapping.start,
+                    name: mapping.device.name().to_owned(),
+                    state: mapping.device.save_state(),
+                })
+                .collect(),
+        }
+    }
 
-                (self.accumulator, self.c_flag) =
-                    self.accumulator.carrying_add(operand, self.c_flag);
+    /// Go back to the state of a snapshot taken with the same ROM and memory map, the history is cleared
+    ///
+    /// Nothing changes when the snapshot is rejected
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let rom_hash = self.rom_hash();
+
+        if snapshot.rom_hash != rom_hash {
+            return Err(SnapshotError::RomMismatch {
+                expected: rom_hash,
+                found: snapshot.rom_hash,
+            });
+        }
 
-                self.v_flag = a_sign == b_sign && a_sign != (self.accumulator > 0x7F);
+        if snapshot.devices.len() != self.bus.mappings().len() {
+            return Err(SnapshotError::Devices(format!(
+                "{} device(s) in the snapshot, {} mapped",
+                snapshot.devices.len(),
+                self.bus.mappings().len()
+            )));
+        }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        let mut bus = self.bus.clone();
 
-                self.pc += 1;
+        for (saved, mapping) in snapshot.devices.iter().zip(bus.mappings_mut()) {
+            if saved.start != mapping.start || saved.name != mapping.device.name() {
+                return Err(SnapshotError::Devices(format!(
+                    "{} at 0x{:02X} in the snapshot, {} at 0x{:02X} mapped",
+                    saved.name,
+                    saved.start,
+                    mapping.device.name(),
+                    mapping.start
+                )));
             }
 
-            // ADC addr
-            0x0F => {
-                let a_sign = self.accumulator > 0x7F;
-                let b_sign = self.ram[operand as usize] > 0x7F;
+            if !mapping.device.load_state(&saved.state) {
+                return Err(SnapshotError::Devices(format!(
+                    "invalid state for the {} at 0x{:02X}",
+                    saved.name, saved.start
+                )));
+            }
+        }
 
-                (self.accumulator, self.c_flag) = self
-                    .accumulator
-                    .carrying_add(self.ram[operand as usize], self.c_flag);
+        *bus.ram_mut() = snapshot.ram;
 
-                self.v_flag = a_sign == b_sign && a_sign != (self.accumulator > 0x7F);
+        self.bus = bus;
+        self.accumulator = snapshot.accumulator;
+        self.flags = snapshot.flags;
+        self.pc = snapshot.pc;
+        self.cycles = snapshot.cycles;
+        self.last_step = None;
+        self.history.clear();
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        Ok(())
+    }
 
-                self.pc += 1;
-            }
+    /// ROM word at the program counter
+    pub const fn current_instruction(&self) -> u16 {
+        self.rom[self.pc as usize]
+    }
 
-            // NEG acc
-            0x10 => {
-                self.accumulator = self.accumulator.wrapping_neg();
+    /// Value of the operand as seen by the instruction, depending on its addressing mode
+    fn operand_value(&self, mode: AddressingMode, operand: u8) -> u8 {
+        match mode {
+            AddressingMode::None => 0,
+            AddressingMode::Acc => self.accumulator,
+            AddressingMode::Const => operand,
+            AddressingMode::Addr => self.bus.read(operand),
+        }
+    }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+    /// Execute a single instruction, traps are handled according to the trap policies
+    pub fn tick(&mut self) -> StepResult {
+        let pc = self.pc;
+        let word = self.rom[pc as usize];
+        let accumulator_before = self.accumulator;
+        let flags_before = self.flags;
+
+        let (jump_target, ram_write) = match nano_chip_isa::decode(word) {
+            Some((info, operand)) => self.execute(*info, operand),
+            None => match self.trap_policies.illegal_opcode {
+                IllegalOpcodePolicy::Stop => {
+                    self.last_step = None;
+                    return StepResult::Trap(Trap::IllegalOpcode { pc, word });
+                }
+                IllegalOpcodePolicy::Nop => (None, None),
+            },
+        };
+
+        let step = StepRecord {
+            cycle: self.cycles,
+            pc,
+            word,
+            accumulator_before,
+            accumulator_after: self.accumulator,
+            flags_before,
+            flags_after: self.flags,
+            ram_write,
+        };
+        self.last_step = Some(step);
+
+        if self.history_limit > 0 {
+            if self.history.len() == self.history_limit {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(step);
+        }
 
-                self.pc += 1;
-            }
+        self.advance(jump_target)
+    }
 
-            // NEG const
-            0x11 => {
-                self.accumulator = operand.wrapping_neg();
+    /// Apply an instruction to the registers and RAM, returns the branch target if the branch is taken
+    fn execute(&mut self, info: InstructionInfo, operand: u8) -> (Option<u8>, Option<RamWrite>) {
+        let value = self.operand_value(info.mode, operand);
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+        let mut jump_target = None;
+        let mut ram_write = None;
 
-                self.pc += 1;
+        match info.mnemonic {
+            Mnemonic::St => {
+                ram_write = Some(RamWrite {
+                    address: operand,
+                    old_value: self.bus.read(operand),
+                    new_value: self.accumulator,
+                });
+                self.bus.write(operand, self.accumulator);
             }
 
-            // NEG addr
-            0x12 => {
-                self.accumulator = self.ram[operand as usize].wrapping_neg();
-
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
-
-                self.pc += 1;
+            Mnemonic::Ld => {
+                self.accumulator = value;
             }
 
-            // INC acc
-            0x13 => {
-                (self.accumulator, self.c_flag) = self.accumulator.overflowing_add(1);
+            Mnemonic::And => {
+                self.accumulator &= value;
+            }
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+            Mnemonic::Or => {
+                self.accumulator |= value;
+            }
 
-                self.pc += 1;
+            Mnemonic::Xor => {
+                self.accumulator ^= value;
             }
 
-            // INC addr
-            0x14 => {
-                (self.accumulator, self.c_flag) = self.ram[operand as usize].overflowing_add(1);
+            Mnemonic::Rol => {
+                let new_c_flag = self.accumulator > 0x7F;
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+                self.accumulator <<= 1;
+                self.accumulator |= u8::from(self.flags.c);
 
-                self.pc += 1;
+                self.flags.c = new_c_flag;
             }
 
-            // DEC acc
-            0x15 => {
-                (self.accumulator, self.c_flag) = self.accumulator.overflowing_sub(1);
+            Mnemonic::Ror => {
+                let new_c_flag = self.accumulator % 2 == 1;
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+                self.accumulator >>= 1;
+                self.accumulator |= if self.flags.c { 0b1000_0000 } else { 0 };
 
-                self.pc += 1;
+                self.flags.c = new_c_flag;
             }
 
-            // DEC addr
-            0x16 => {
-                (self.accumulator, self.c_flag) = self.ram[operand as usize].overflowing_sub(1);
+            Mnemonic::Add | Mnemonic::Adc => {
+                let a_sign = self.accumulator > 0x7F;
+                let b_sign = value > 0x7F;
+                let carry_in = info.mnemonic == Mnemonic::Adc && self.flags.c;
 
-                self.z_flag = self.accumulator == 0;
-                self.n_flag = self.accumulator > 0x7F;
+                (self.accumulator, self.flags.c) = self.accumulator.carrying_add(value, carry_in);
 
-                self.pc += 1;
+                self.flags.v = a_sign == b_sign && a_sign != (self.accumulator > 0x7F);
             }
 
-            // SETC
-            0x17 => {
-                self.c_flag = true;
-
-                self.pc += 1;
+            Mnemonic::Neg => {
+                self.accumulator = value.wrapping_neg();
             }
 
-            // CLRC
-            0x18 => {
-                self.c_flag = false;
-
-                self.pc += 1;
+            Mnemonic::Inc => {
+                (self.accumulator, self.flags.c) = value.overflowing_add(1);
             }
 
-            // TRFNC
-            0x19 => {
-                self.c_flag = self.n_flag;
-
-                self.pc += 1;
+            Mnemonic::Dec => {
+                (self.accumulator, self.flags.c) = value.overflowing_sub(1);
             }
 
-            // BZ0
-            0x1A => {
-                if self.z_flag {
-                    self.pc += 1;
-                } else {
-                    self.pc = operand;
-                }
+            Mnemonic::Setc => {
+                self.flags.c = true;
             }
 
-            // BZ1
-            0x1B => {
-                if self.z_flag {
-                    self.pc = operand;
-                } else {
-                    self.pc += 1;
-                }
+            Mnemonic::Clrc => {
+                self.flags.c = false;
             }
 
-            // BC0
-            0x1C => {
-                if self.c_flag {
-                    self.pc += 1;
-                } else {
-                    self.pc = operand;
-                }
+            Mnemonic::Trfnc => {
+                self.flags.c = self.flags.n;
             }
 
-            // BC1
-            0x1D => {
-                if self.c_flag {
-                    self.pc = operand;
-                } else {
-                    self.pc += 1;
+            Mnemonic::Bz0
+            | Mnemonic::Bz1
+            | Mnemonic::Bc0
+            | Mnemonic::Bc1
+            | Mnemonic::Bv0
+            | Mnemonic::Bv1
+            | Mnemonic::Bn0
+            | Mnemonic::Bn1 => {
+                if let Some((flag, taken_value)) = info.mnemonic.branch_condition() {
+                    if self.flags.get(flag) == taken_value {
+                        jump_target = Some(operand);
+                    }
                 }
             }
 
-            // BV0
-            0x1E => {
-                if self.v_flag {
-                    self.pc += 1;
-                } else {
-                    self.pc = operand;
-                }
+            Mnemonic::Bra => {
+                jump_target = Some(operand);
             }
 
-            // BV1
-            0x1F => {
-                if self.v_flag {
-                    self.pc = operand;
-                } else {
-                    self.pc += 1;
-                }
+            Mnemonic::Nop => {}
+        }
+
+        if info.flags.z {
+            self.flags.z = self.accumulator == 0;
+        }
+
+        if info.flags.n {
+            self.flags.n = self.accumulator > 0x7F;
+        }
+
+        (jump_target, ram_write)
+    }
+
+    /// Move the program counter to the next instruction
+    const fn advance(&mut self, jump_target: Option<u8>) -> StepResult {
+        let pc = self.pc;
+        self.cycles += 1;
+
+        if let Some(target) = jump_target {
+            self.pc = target;
+
+            return match self.trap_policies.halt {
+                HaltPolicy::Stop if target == pc => StepResult::Trap(Trap::Halted { pc }),
+                _ => StepResult::Ok,
+            };
+        }
+
+        let (next_pc, wrapped) = pc.overflowing_add(1);
+        self.pc = next_pc;
+
+        match self.trap_policies.pc_wrap {
+            PcWrapPolicy::Stop if wrapped => StepResult::Trap(Trap::PcWrap),
+            _ => StepResult::Ok,
+        }
+    }
+
+    /// Execute the given number of instructions, stops early on the first reported trap
+    pub fn run(&mut self, cycles: u64) -> StepResult {
+        for _ in 0..cycles {
+            if let StepResult::Trap(trap) = self.tick() {
+                return StepResult::Trap(trap);
             }
+        }
+
+        StepResult::Ok
+    }
 
-            // BN0
-            0x20 => {
-                if self.n_flag {
-                    self.pc += 1;
-                } else {
-                    self.pc = operand;
+    /// Run until the program halts, a trap is reported or `max_cycles` instructions were executed
+    ///
+    /// Besides a branch to itself, a loop is considered halted when a whole iteration,
+    /// from one backward branch to the next, leaves the CPU and RAM unchanged
+    pub fn run_to_halt(&mut self, max_cycles: u64) -> RunOutcome {
+        self.run_to_halt_with(max_cycles, |_| {})
+    }
+
+    /// Same as `run_to_halt`, `on_step` is called with the record of every executed instruction
+    pub fn run_to_halt_with<F>(&mut self, max_cycles: u64, mut on_step: F) -> RunOutcome
+    where
+        F: FnMut(&StepRecord),
+    {
+        let trap_policies = self.trap_policies;
+        self.trap_policies.halt = HaltPolicy::Stop;
+
+        let mut loop_state = None;
+        let mut outcome = RunOutcome::Timeout;
+
+        for _ in 0..max_cycles {
+            let pc = self.pc;
+            let result = self.tick();
+
+            // An illegal opcode that stops leaves no record
+            if let Some(step) = &self.last_step {
+                on_step(step);
+
+                // Writing to a device is visible outside, so the loop isn't idle
+                if step
+                    .ram_write
+                    .is_some_and(|write| self.bus.device_at(write.address).is_some())
+                {
+                    loop_state = None;
                 }
             }
 
-            // BN1
-            0x21 => {
-                if self.n_flag {
-                    self.pc = operand;
-                } else {
-                    self.pc += 1;
+            match result {
+                StepResult::Trap(Trap::Halted { pc }) => {
+                    outcome = RunOutcome::Halted { pc };
+                    break;
                 }
+                StepResult::Trap(trap) => {
+                    outcome = RunOutcome::Trap(trap);
+                    break;
+                }
+                StepResult::Ok => {}
             }
 
-            // BRA
-            0x22 => {
-                self.pc = operand;
-            }
+            if self.pc < pc {
+                let state = (self.pc, self.accumulator, self.flags, *self.bus.ram());
 
-            // NOP
-            0x3F => {
-                self.pc += 1;
-            }
+                if loop_state == Some(state) {
+                    outcome = RunOutcome::Halted { pc: self.pc };
+                    break;
+                }
 
-            _ => {
-                panic!("Unknown opcode {opcode}");
+                loop_state = Some(state);
             }
         }
+
+        self.trap_policies = trap_policies;
+        outcome
     }
 
-    pub fn print_status(&self) {
-        for y in 0..16 {
-            for x in 0..16 {
-                print!("{:3x}", self.ram[y * 16 + x]);
+    /// RAM cells between `start` and `end` in hexadecimal, 16 per line after their address
+    pub fn ram_dump(&self, start: u8, end: usize) -> String {
+        let start = usize::from(start);
+        let mut dump = String::new();
+
+        let end = end.clamp(start, RAM_SIZE);
+        let values = (start..end)
+            .filter_map(|address| u8::try_from(address).ok())
+            .map(|address| self.bus.read(address))
+            .collect::<Vec<_>>();
+
+        for (row, values) in values.chunks(16).enumerate() {
+            if row > 0 {
+                dump.push('\n');
+            }
+
+            let _ = write!(dump, "0x{:02X}:", start + row * 16);
+
+            for value in values {
+                let _ = write!(dump, " {value:02X}");
             }
-            println!();
         }
 
-        println!();
-        println!("Accumulator : {:x}", self.accumulator);
-        println!("Program Counter : {:x}", self.pc);
-        println!("Opcode : {:x}", self.rom[self.pc as usize] >> 8);
-        println!("Operand : {:x}", self.rom[self.pc as usize] & 0xFF);
+        dump
     }
 }
@@ -0,0 +1,244 @@
+use std::fmt::Write;
+
+use nano_chip_isa::{AddressingMode, InstructionInfo, Mnemonic, RAM_SIZE, ROM_SIZE};
+
+use crate::value::{labels, Symbols};
+use crate::StepRecord;
+
+/// Number of opcodes, they are 6 bits wide
+const OPCODES: usize = 64;
+
+/// Share of `count` in `total` rounded to one decimal, like ` 12.5%`
+fn percentage(count: u64, total: u64) -> String {
+    let tenths = (count * 1000 + total / 2).checked_div(total).unwrap_or(0);
+    format!("{:>3}.{}%", tenths / 10, tenths % 10)
+}
+
+/// Instruction without its operand, like `LD [addr]` or `INC ACC`
+fn opcode_name(info: InstructionInfo) -> String {
+    let operand = match info.mode {
+        AddressingMode::None => "",
+        AddressingMode::Acc => " ACC",
+        AddressingMode::Const if info.mnemonic.is_branch() => " target",
+        AddressingMode::Const => " value",
+        AddressingMode::Addr => " [addr]",
+    };
+
+    format!("{}{operand}", info.mnemonic.name())
+}
+
+/// Executions per ROM address, opcode and labelled region, and accesses per RAM address, filled with `record`
+///
+/// Every instruction takes one cycle, so execution counts are cycle counts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    cycles: u64,
+    executed: [u64; ROM_SIZE],
+    opcodes: [u64; OPCODES],
+    /// Words the CPU doesn't know, executed like `NOP`
+    illegal: u64,
+    ram_reads: [u64; RAM_SIZE],
+    ram_writes: [u64; RAM_SIZE],
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    pub const fn new() -> Self {
+        Self {
+            cycles: 0,
+            executed: [0; ROM_SIZE],
+            opcodes: [0; OPCODES],
+            illegal: 0,
+            ram_reads: [0; RAM_SIZE],
+            ram_writes: [0; RAM_SIZE],
+        }
+    }
+
+    /// Count an executed instruction, for `run_to_halt_with`
+    pub fn record(&mut self, step: &StepRecord) {
+        self.cycles += 1;
+        self.executed[usize::from(step.pc)] += 1;
+
+        match nano_chip_isa::decode(step.word) {
+            Some((info, operand)) => {
+                self.opcodes[usize::from(info.opcode)] += 1;
+
+                if info.mode == AddressingMode::Addr && info.mnemonic != Mnemonic::St {
+                    self.ram_reads[usize::from(operand)] += 1;
+                }
+            }
+            None => self.illegal += 1,
+        }
+
+        if let Some(write) = step.ram_write {
+            self.ram_writes[usize::from(write.address)] += 1;
+        }
+    }
+
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Number of times the instruction at `address` was executed
+    pub const fn executed(&self, address: u8) -> u64 {
+        self.executed[address as usize]
+    }
+
+    /// Number of instructions that read and wrote the RAM cell at `address`
+    pub const fn ram_accesses(&self, address: u8) -> (u64, u64) {
+        (
+            self.ram_reads[address as usize],
+            self.ram_writes[address as usize],
+        )
+    }
+
+    /// Cycles spent from each label to the next one, the code before the first label is `(start)`
+    fn regions<'a>(&self, symbols: &'a Symbols) -> Vec<(&'a str, u64)> {
+        let labels = labels(symbols);
+
+        let mut regions = Vec::new();
+        let start = labels
+            .first()
+            .map_or(ROM_SIZE, |(start, _)| usize::from(*start));
+        let before = self.executed[..start].iter().sum::<u64>();
+
+        if before > 0 {
+            regions.push(("(start)", before));
+        }
+
+        for (index, (start, name)) in labels.iter().enumerate() {
+            let end = labels
+                .get(index + 1)
+                .map_or(ROM_SIZE, |(end, _)| usize::from(*end));
+
+            regions.push((*name, self.executed[usize::from(*start)..end].iter().sum()));
+        }
+
+        regions
+    }
+
+    /// Hot spots sorted by cycles, per address, label and opcode, then the RAM accesses
+    pub fn report(&self, rom: &[u16; ROM_SIZE], symbols: &Symbols) -> String {
+        let total = self.cycles;
+        let labels = labels(symbols);
+        let label_of = |address: u8| {
+            labels
+                .iter()
+                .rev()
+                .find(|(start, _)| *start <= address)
+                .map_or("(start)", |(_, name)| name)
+        };
+
+        let mut report = String::new();
+        let _ = writeln!(report, "Total cycles : {total}");
+
+        let mut addresses = (0..=u8::MAX)
+            .filter(|&address| self.executed(address) > 0)
+            .collect::<Vec<_>>();
+        addresses.sort_by_key(|&address| std::cmp::Reverse(self.executed(address)));
+
+        let _ = writeln!(report);
+        let _ = writeln!(report, "Hot spots");
+        let _ = writeln!(report, "  Cycles       %  Addr  Label         Instruction");
+
+        for address in addresses {
+            let word = rom[usize::from(address)];
+            let instruction = nano_chip_isa::decode(word).map_or_else(
+                || format!(".word 0x{word:04X}"),
+                |(info, operand)| info.format(operand),
+            );
+
+            let _ = writeln!(
+                report,
+                "{:>8}  {}  0x{address:02X}  {:<12}  {instruction}",
+                self.executed(address),
+                percentage(self.executed(address), total),
+                label_of(address)
+            );
+        }
+
+        let mut regions = self.regions(symbols);
+        regions.retain(|(_, cycles)| *cycles > 0);
+        regions.sort_by_key(|(_, cycles)| std::cmp::Reverse(*cycles));
+
+        let _ = writeln!(report);
+        let _ = writeln!(report, "Labels");
+        let _ = writeln!(report, "  Cycles       %  Label");
+
+        for (name, cycles) in regions {
+            let _ = writeln!(report, "{cycles:>8}  {}  {name}", percentage(cycles, total));
+        }
+
+        let mut opcodes = (0..OPCODES)
+            .filter_map(|opcode| {
+                let (info, _) = nano_chip_isa::decode(u16::try_from(opcode).ok()? << 8)?;
+                let count = self.opcodes[opcode];
+                (count > 0).then(|| (opcode_name(*info), count))
+            })
+            .collect::<Vec<_>>();
+
+        if self.illegal > 0 {
+            opcodes.push(("illegal".to_owned(), self.illegal));
+        }
+
+        opcodes.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+        let _ = writeln!(report);
+        let _ = writeln!(report, "Opcodes");
+        let _ = writeln!(report, "  Cycles       %  Opcode");
+
+        for (name, count) in opcodes {
+            let _ = writeln!(report, "{count:>8}  {}  {name}", percentage(count, total));
+        }
+
+        let _ = writeln!(report);
+        let _ = writeln!(report, "RAM");
+        let _ = writeln!(report, "Addr     Reads    Writes");
+
+        for address in 0..=u8::MAX {
+            let (reads, writes) = self.ram_accesses(address);
+
+            if reads > 0 || writes > 0 {
+                let _ = writeln!(report, "0x{address:02X}  {reads:>8}  {writes:>8}");
+            }
+        }
+
+        report
+    }
+
+    /// Folded stacks for flamegraph tools, one line per executed instruction under its label,
+    /// like `loop_start;0x03 BZ1 0x0A 12`
+    pub fn folded(&self, rom: &[u16; ROM_SIZE], symbols: &Symbols) -> String {
+        let labels = labels(symbols);
+        let mut folded = String::new();
+
+        for address in 0..=u8::MAX {
+            let count = self.executed(address);
+
+            if count == 0 {
+                continue;
+            }
+
+            let label = labels
+                .iter()
+                .rev()
+                .find(|(start, _)| *start <= address)
+                .map_or("(start)", |(_, name)| name.trim_start_matches(':'));
+
+            let word = rom[usize::from(address)];
+            let instruction = nano_chip_isa::decode(word).map_or_else(
+                || format!(".word 0x{word:04X}"),
+                |(info, operand)| info.format(operand),
+            );
+
+            let _ = writeln!(folded, "{label};0x{address:02X} {instruction} {count}");
+        }
+
+        folded
+    }
+}
@@ -0,0 +1,254 @@
+use std::fmt::{self, Write};
+use std::path::Path;
+
+use nano_chip_isa::{Flag, Flags, RAM_SIZE};
+
+use crate::hex::{decode_hex, encode_hex};
+use crate::value::{parse_value, Symbols};
+
+/// Version written in the first line of snapshot files, older or newer files are rejected
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// First word of snapshot files
+const MAGIC: &str = "nano_chip_snapshot";
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Read(std::io::Error),
+    Parse {
+        /// Line number, starting at 1
+        line: usize,
+        message: String,
+    },
+    /// The file was written by another version of the emulator, holds its version
+    Version(u32),
+    /// The snapshot was taken with another ROM, holds both hashes
+    RomMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// The devices of the snapshot aren't the ones of the memory map
+    Devices(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(read_error) => write!(f, "can't read snapshot : {read_error}"),
+            Self::Parse { line, message } => write!(f, "snapshot line {line} : {message}"),
+            Self::Version(version) => write!(
+                f,
+                "snapshot version {version} isn't supported, expected {SNAPSHOT_VERSION}"
+            ),
+            Self::RomMismatch { expected, found } => write!(
+                f,
+                "snapshot taken with another ROM, hash {found:016X} instead of {expected:016X}"
+            ),
+            Self::Devices(message) => write!(f, "snapshot devices don't match : {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Saved state of a memory mapped device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceState {
+    pub start: u8,
+    /// Kind of device, as given by `Device::name`
+    pub name: String,
+    /// Bytes given by `Device::save_state`
+    pub state: Vec<u8>,
+}
+
+/// Whole state of an emulator, taken with `NanoChipEmulator::snapshot`
+///
+/// The ROM isn't saved, only its hash to check that the snapshot is restored on the same program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub rom_hash: u64,
+    /// Plain RAM, the cells under a device included
+    pub ram: [u8; RAM_SIZE],
+    pub accumulator: u8,
+    pub flags: Flags,
+    pub pc: u8,
+    pub cycles: u64,
+    pub devices: Vec<DeviceState>,
+}
+
+impl Snapshot {
+    /// Read a snapshot file written with `save`
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let text = std::fs::read_to_string(path).map_err(SnapshotError::Read)?;
+        Self::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Read the text written by `Display`
+    pub fn parse(text: &str) -> Result<Self, SnapshotError> {
+        let mut version = None;
+        let mut rom_hash = None;
+        let mut accumulator = None;
+        let mut flags = None;
+        let mut pc = None;
+        let mut cycles = None;
+        let mut ram = [0; RAM_SIZE];
+        let mut ram_rows = [false; RAM_SIZE / 16];
+        let mut devices = Vec::new();
+        let symbols = Symbols::new();
+
+        for (line_n, line) in (1..).zip(text.lines()) {
+            let code = line.split_once(';').map_or(line, |(code, _)| code);
+            let error = |message: String| SnapshotError::Parse {
+                line: line_n,
+                message,
+            };
+            let byte = |text: &str| -> Result<u8, SnapshotError> {
+                parse_value(text, &symbols).map_err(|value_error| error(value_error.to_string()))
+            };
+
+            let words = code.split_whitespace().collect::<Vec<_>>();
+
+            let Some((key, values)) = words.split_first() else {
+                continue;
+            };
+
+            // The version comes first, the rest of the format depends on it
+            if version.is_none() {
+                let (MAGIC, [value]) = (*key, values) else {
+                    return Err(error(format!("expected `{MAGIC} {SNAPSHOT_VERSION}`")));
+                };
+                let parsed = value
+                    .parse()
+                    .map_err(|_| error(format!("invalid version `{value}`")))?;
+
+                if parsed != SNAPSHOT_VERSION {
+                    return Err(SnapshotError::Version(parsed));
+                }
+
+                version = Some(parsed);
+                continue;
+            }
+
+            match (*key, values) {
+                ("rom", [hash]) => {
+                    rom_hash = Some(
+                        u64::from_str_radix(hash, 16)
+                            .map_err(|_| error(format!("invalid ROM hash `{hash}`")))?,
+                    );
+                }
+                ("acc", [value]) => accumulator = Some(byte(value)?),
+                ("pc", [value]) => pc = Some(byte(value)?),
+                ("flags", [value]) => {
+                    if value.len() != 4 || !value.chars().all(|c| "ZCVN-".contains(c)) {
+                        return Err(error(format!("invalid flags `{value}`")));
+                    }
+
+                    let mut parsed = Flags::NONE;
+                    for flag in Flag::ALL {
+                        parsed.set(flag, value.contains(flag.name()));
+                    }
+                    flags = Some(parsed);
+                }
+                ("cycles", [value]) => {
+                    cycles = Some(
+                        value
+                            .parse()
+                            .map_err(|_| error(format!("invalid cycle count `{value}`")))?,
+                    );
+                }
+                ("ram", [start, row @ ..]) => {
+                    let start = usize::from(byte(start)?);
+
+                    if !start.is_multiple_of(16) || row.len() != 16 {
+                        return Err(error(
+                            "expected a multiple of 16 and 16 bytes after ram".to_owned(),
+                        ));
+                    }
+
+                    for (offset, value) in row.iter().enumerate() {
+                        ram[start + offset] = u8::from_str_radix(value, 16)
+                            .map_err(|_| error(format!("invalid byte `{value}`")))?;
+                    }
+                    ram_rows[start / 16] = true;
+                }
+                ("device", [start, name, state @ ..]) => {
+                    let state = match state {
+                        [] => Vec::new(),
+                        [hex] => decode_hex(hex)
+                            .ok_or_else(|| error(format!("invalid device state `{hex}`")))?,
+                        _ => return Err(error("too many words for a device".to_owned())),
+                    };
+
+                    devices.push(DeviceState {
+                        start: byte(start)?,
+                        name: (*name).to_owned(),
+                        state,
+                    });
+                }
+                _ => return Err(error(format!("unexpected `{}`", code.trim()))),
+            }
+        }
+
+        let end = text.lines().count();
+        let missing = |field: &str| SnapshotError::Parse {
+            line: end,
+            message: format!("missing {field}"),
+        };
+
+        if version.is_none() {
+            return Err(missing("version"));
+        }
+
+        if ram_rows.contains(&false) {
+            return Err(missing("RAM rows"));
+        }
+
+        Ok(Self {
+            rom_hash: rom_hash.ok_or_else(|| missing("rom"))?,
+            ram,
+            accumulator: accumulator.ok_or_else(|| missing("acc"))?,
+            flags: flags.ok_or_else(|| missing("flags"))?,
+            pc: pc.ok_or_else(|| missing("pc"))?,
+            cycles: cycles.ok_or_else(|| missing("cycles"))?,
+            devices,
+        })
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{MAGIC} {SNAPSHOT_VERSION}")?;
+        writeln!(f, "rom {:016X}", self.rom_hash)?;
+        writeln!(f, "pc 0x{:02X}", self.pc)?;
+        writeln!(f, "acc 0x{:02X}", self.accumulator)?;
+        writeln!(f, "flags {}", self.flags.letters())?;
+        writeln!(f, "cycles {}", self.cycles)?;
+
+        for (row, values) in self.ram.chunks(16).enumerate() {
+            let mut line = format!("ram 0x{:02X}", row * 16);
+
+            for value in values {
+                let _ = write!(line, " {value:02X}");
+            }
+
+            writeln!(f, "{line}")?;
+        }
+
+        for device in &self.devices {
+            let mut line = format!("device 0x{:02X} {}", device.start, device.name);
+
+            if !device.state.is_empty() {
+                line.push(' ');
+                line.push_str(&encode_hex(&device.state));
+            }
+
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
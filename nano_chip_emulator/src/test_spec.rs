@@ -0,0 +1,358 @@
+use std::fmt;
+
+use nano_chip_isa::Flag;
+
+use crate::value::{parse_value, Symbols};
+use crate::{NanoChipEmulator, RunOutcome, TrapPolicies};
+
+/// Cycle budget of a test case that doesn't give one
+pub const DEFAULT_TEST_CYCLES: u64 = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} : {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// Test spec file : the program to test and the text of its test cases
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestSpec {
+    /// Path of the ROM image or `.asm` source, relative to the spec file
+    pub program: String,
+    /// Line of each test case and its text after `test`
+    pub cases: Vec<(usize, String)>,
+}
+
+impl TestSpec {
+    /// Parse a spec file, the test cases are parsed once the program symbols are known
+    ///
+    /// ```text
+    /// program multiply.asm
+    /// test three_by_four ram[$a]=3 ram[$b]=4 expect ram[$result]=12 cycles<200
+    /// test zero ram[$a]=0 ram[$b]=7
+    ///     expect ram[$result]=0 z=1   ; indented lines continue the test above
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, SpecError> {
+        let mut program = None;
+        let mut cases = Vec::<(usize, String)>::new();
+
+        for (line_n, line) in (1..).zip(text.lines()) {
+            let code = line.split_once(';').map_or(line, |(code, _)| code);
+            let error = |message: &str| SpecError {
+                line: line_n,
+                message: message.to_owned(),
+            };
+
+            if code.trim().is_empty() {
+                continue;
+            }
+
+            if code.starts_with(char::is_whitespace) {
+                let (_, case) = cases
+                    .last_mut()
+                    .ok_or_else(|| error("indented line outside of a test"))?;
+                case.push(' ');
+                case.push_str(code.trim());
+                continue;
+            }
+
+            let (keyword, rest) = code
+                .trim()
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| error("expected `program <file>` or `test <name> ...`"))?;
+
+            match keyword {
+                "program" if program.is_none() => program = Some(rest.trim().to_owned()),
+                "program" => return Err(error("the program is already given")),
+                "test" => cases.push((line_n, rest.trim().to_owned())),
+                _ => return Err(error(&format!("unknown keyword `{keyword}`"))),
+            }
+        }
+
+        Ok(Self {
+            program: program.ok_or_else(|| SpecError {
+                line: 1,
+                message: "no `program <file>` line".to_owned(),
+            })?,
+            cases,
+        })
+    }
+}
+
+/// A value of the CPU state set before a test or checked after it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Ram(u8),
+    Accumulator,
+    ProgramCounter,
+    Flag(Flag),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ram(address) => write!(f, "ram[0x{address:02X}]"),
+            Self::Accumulator => write!(f, "acc"),
+            Self::ProgramCounter => write!(f, "pc"),
+            Self::Flag(flag) => write!(f, "{}", flag.name().to_lowercase()),
+        }
+    }
+}
+
+impl Target {
+    /// Current value, 0 or 1 for a flag
+    pub fn read(self, emulator: &NanoChipEmulator) -> u8 {
+        match self {
+            Self::Ram(address) => emulator.read_ram(address),
+            Self::Accumulator => emulator.accumulator(),
+            Self::ProgramCounter => emulator.pc(),
+            Self::Flag(flag) => u8::from(emulator.flags().get(flag)),
+        }
+    }
+
+    fn write(self, emulator: &mut NanoChipEmulator, value: u8) {
+        match self {
+            Self::Ram(address) => emulator.write_ram(address, value),
+            Self::Accumulator => emulator.set_accumulator(value),
+            Self::ProgramCounter => emulator.set_pc(value),
+            Self::Flag(flag) => {
+                let mut flags = emulator.flags();
+                flags.set(flag, value != 0);
+                emulator.set_flags(flags);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestCase {
+    pub name: String,
+    /// Line of the test in its file
+    pub line: usize,
+    /// Values written before running
+    pub setup: Vec<(Target, u8)>,
+    /// Values expected once the program halted
+    pub expected: Vec<(Target, u8)>,
+    /// The program must halt within this number of cycles
+    pub max_cycles: u64,
+}
+
+impl TestCase {
+    /// Parse the text of a test case : its name, then `target=value` settings,
+    /// then `expect` followed by the expected values, with `cycles<N` anywhere
+    ///
+    /// Targets are `ram[addr]`, `acc`, `pc`, the flags `z`, `c`, `v` and `n` and `flags`
+    /// which gives every flag at once, like `flags=ZC`
+    pub fn parse(line: usize, text: &str, symbols: &Symbols) -> Result<Self, SpecError> {
+        let error = |message: String| SpecError { line, message };
+
+        let mut words = text.split_whitespace();
+        let name = words
+            .next()
+            .ok_or_else(|| error("the test needs a name".to_owned()))?;
+
+        let mut case = Self {
+            name: name.to_owned(),
+            line,
+            setup: Vec::new(),
+            expected: Vec::new(),
+            max_cycles: DEFAULT_TEST_CYCLES,
+        };
+        let mut expecting = false;
+
+        for word in words {
+            if word == "expect" {
+                expecting = true;
+                continue;
+            }
+
+            if let Some(budget) = word.strip_prefix("cycles<") {
+                let (inclusive, budget) = budget
+                    .strip_prefix('=')
+                    .map_or((false, budget), |budget| (true, budget));
+                let budget = budget
+                    .parse::<u64>()
+                    .map_err(|_| error(format!("invalid cycle budget `{word}`")))?;
+
+                case.max_cycles = if inclusive {
+                    budget
+                } else {
+                    budget.saturating_sub(1)
+                };
+                continue;
+            }
+
+            let (target, value) = word
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `target=value`, got `{word}`")))?;
+
+            let values = if let Some(ram_address) = target
+                .strip_prefix("ram[")
+                .and_then(|address| address.strip_suffix(']'))
+            {
+                let address =
+                    parse_value(ram_address, symbols).map_err(|err| error(err.to_string()))?;
+                let value = parse_value(value, symbols).map_err(|err| error(err.to_string()))?;
+                vec![(Target::Ram(address), value)]
+            } else {
+                match target {
+                    "acc" | "pc" => {
+                        let value =
+                            parse_value(value, symbols).map_err(|err| error(err.to_string()))?;
+                        let target = if target == "acc" {
+                            Target::Accumulator
+                        } else {
+                            Target::ProgramCounter
+                        };
+                        vec![(target, value)]
+                    }
+                    "flags" => {
+                        let set = value.to_uppercase();
+
+                        if !set.chars().all(|c| "ZCVN-".contains(c)) {
+                            return Err(error(format!(
+                                "invalid flags `{value}`, expected letters among ZCVN or -"
+                            )));
+                        }
+
+                        Flag::ALL
+                            .iter()
+                            .map(|flag| (Target::Flag(*flag), u8::from(set.contains(flag.name()))))
+                            .collect()
+                    }
+                    _ => {
+                        let flag = Flag::ALL
+                            .into_iter()
+                            .find(|flag| flag.name().eq_ignore_ascii_case(target))
+                            .ok_or_else(|| error(format!("unknown target `{target}`")))?;
+                        let value = match value {
+                            "0" => 0,
+                            "1" => 1,
+                            _ => {
+                                return Err(error(format!("flag values are 0 or 1, got `{value}`")))
+                            }
+                        };
+                        vec![(Target::Flag(flag), value)]
+                    }
+                }
+            };
+
+            if expecting {
+                case.expected.extend(values);
+            } else {
+                case.setup.extend(values);
+            }
+        }
+
+        Ok(case)
+    }
+
+    /// Run the test on a fresh copy of `emulator`
+    pub fn run(&self, emulator: &NanoChipEmulator) -> CaseResult {
+        let mut emulator = emulator.clone();
+        emulator.reset();
+        emulator.set_trap_policies(TrapPolicies::STOP);
+
+        for (target, value) in &self.setup {
+            target.write(&mut emulator, *value);
+        }
+
+        let outcome = emulator.run_to_halt(self.max_cycles);
+
+        let mismatches = self
+            .expected
+            .iter()
+            .filter_map(|&(target, expected)| {
+                let actual = target.read(&emulator);
+                (actual != expected).then_some(Mismatch {
+                    target,
+                    expected,
+                    actual,
+                })
+            })
+            .collect();
+
+        CaseResult {
+            name: self.name.clone(),
+            line: self.line,
+            outcome,
+            cycles: emulator.cycles(),
+            mismatches,
+        }
+    }
+}
+
+/// A checked value that differs from the expectation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub target: Target,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if matches!(self.target, Target::Flag(_)) {
+            write!(
+                f,
+                "{} : expected {}, got {}",
+                self.target, self.expected, self.actual
+            )
+        } else {
+            write!(
+                f,
+                "{} : expected 0x{:02X} ({}), got 0x{:02X} ({})",
+                self.target, self.expected, self.expected, self.actual, self.actual
+            )
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaseResult {
+    pub name: String,
+    pub line: usize,
+    pub outcome: RunOutcome,
+    pub cycles: u64,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl CaseResult {
+    /// The program halted in time and every expected value matched
+    pub const fn passed(&self) -> bool {
+        matches!(self.outcome, RunOutcome::Halted { .. }) && self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for CaseResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return write!(f, "PASS {} ({} cycles)", self.name, self.cycles);
+        }
+
+        write!(f, "FAIL {} (line {})", self.name, self.line)?;
+
+        match self.outcome {
+            RunOutcome::Halted { .. } => {}
+            RunOutcome::Timeout => write!(f, "\n  did not halt within {} cycles", self.cycles)?,
+            RunOutcome::Trap(trap) => {
+                write!(f, "\n  stopped after {} cycles, {trap}", self.cycles)?;
+            }
+        }
+
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {mismatch}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1174 @@
+#![cfg(test)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use nano_chip_isa::{Flag, ROM_SIZE};
+
+use crate::{
+    load_memory_map, load_program, parse_value, Bus, BusError, Condition, ConditionError, Console,
+    Coverage, Debugger, DebuggerError, Flags, GdbStub, HaltPolicy, IllegalOpcodePolicy, Key, Leds,
+    LoadError, MemoryMapError, Mismatch, NanoChipEmulator, PcWrapPolicy, Profile, RamWrite, Reply,
+    RomError, RunOutcome, SevenSegment, Snapshot, SnapshotError, SpecError, StepRecord, StepResult,
+    Switches, Symbols, Target, TestCase, TestSpec, TraceFormat, TraceWriter, Trap, TrapPolicies,
+    Tui, ValueError, VcdConfig, VcdWriter, WatchKind, DEFAULT_SPEED, DEFAULT_TEST_CYCLES,
+    TARGET_XML,
+};
+
+fn emulator(source: &str) -> NanoChipEmulator {
+    let program = nano_chip_assembler::assemble(source).unwrap();
+    NanoChipEmulator::from_bytes(&program.to_bytes()).unwrap()
+}
+
+#[test]
+fn test_from_bytes() {
+    let emulator = NanoChipEmulator::from_bytes(&[0x02, 0x2A, 0x3F, 0x00]).unwrap();
+    assert_eq!(emulator.rom()[..3], [0x022A, 0x3F00, 0]);
+
+    assert!(NanoChipEmulator::from_bytes(&[0; ROM_SIZE * 2 + 2]).is_err());
+    assert!(matches!(
+        NanoChipEmulator::from_bytes(&[0x02, 0x2A, 0x3F]),
+        Err(RomError::OddLength(3))
+    ));
+}
+
+#[test]
+fn test_load_store() {
+    let mut emulator = emulator("LD 42\nST [7]\nLD 0");
+    emulator.run(2);
+    assert_eq!(emulator.read_ram(7), 42);
+    assert_eq!(emulator.pc(), 2);
+
+    emulator.tick();
+    assert_eq!(emulator.accumulator(), 0);
+    assert!(emulator.flags().z);
+}
+
+#[test]
+fn test_add_flags() {
+    let mut emulator = emulator("LD 127\nADD 1\nADD 128");
+    emulator.run(2);
+    assert_eq!(emulator.accumulator(), 128);
+    assert_eq!(emulator.flags(), Flags::new(false, false, true, true));
+
+    emulator.tick();
+    assert_eq!(emulator.accumulator(), 0);
+    assert_eq!(emulator.flags(), Flags::new(true, true, true, false));
+}
+
+#[test]
+fn test_adc_rol() {
+    let mut emulator = emulator("SETC\nLD 1\nADC 1\nROL ACC\nROR ACC");
+    emulator.run(3);
+    assert_eq!(emulator.accumulator(), 3);
+    assert!(!emulator.flags().c);
+
+    emulator.set_flags(Flags::C);
+    emulator.tick();
+    assert_eq!(emulator.accumulator(), 7);
+
+    emulator.tick();
+    assert_eq!(emulator.accumulator(), 3);
+    assert!(emulator.flags().c);
+}
+
+#[test]
+fn test_branches() {
+    let mut emulator = emulator("LD 0\nBZ1 3\nNOP\nBN1 0\nBRA 1");
+    emulator.run(2);
+    assert_eq!(emulator.pc(), 3);
+
+    emulator.tick();
+    assert_eq!(emulator.pc(), 4);
+
+    emulator.tick();
+    assert_eq!(emulator.pc(), 1);
+}
+
+#[test]
+fn test_fibonacci() {
+    let mut emulator = emulator(include_str!("../../examples/fibonacci.asm"));
+    emulator.run(3 + 8 * 6);
+    assert_eq!(emulator.ram()[..3], [13, 21, 21]);
+
+    emulator.reset();
+    assert_eq!(emulator.ram()[..3], [0, 0, 0]);
+    assert_eq!(emulator.pc(), 0);
+}
+
+#[test]
+fn test_debugger() {
+    let mut debugger = Debugger::new(emulator(include_str!("../../examples/fibonacci.asm")));
+    debugger.add_symbol(":loop_start", 3);
+    debugger.add_symbol("$c", 2);
+
+    let mut output = |line: &str| match debugger.execute(line) {
+        Ok(Reply::Output(output)) => output,
+        other => panic!("unexpected reply {other:?}"),
+    };
+
+    assert_eq!(
+        output("break :loop_start"),
+        "Breakpoint at 0x03 <:loop_start>"
+    );
+    assert_eq!(
+        output("continue"),
+        "Breakpoint at 0x03\n0x03 <:loop_start>  LD [0x00]"
+    );
+    assert_eq!(
+        output("continue"),
+        "Breakpoint at 0x03\n0x03 <:loop_start>  LD [0x00]"
+    );
+    assert_eq!(output("print [$c]"), "[0x02] <$c> = 0x02 (2)");
+    assert_eq!(output("step 2"), "0x05  ST [$c]");
+    assert_eq!(output(""), "0x07  ST [0x00]");
+    assert_eq!(output("print acc"), "acc = 0x02 (2)");
+    assert_eq!(output("set acc = 0x80"), "");
+    assert_eq!(output("print acc"), "acc = 0x80 (128)");
+    assert_eq!(output("set [0x10]=-1"), "");
+    assert_eq!(output("mem 0x0E..=0x11"), "0x0E: 00 00 FF 00");
+    assert_eq!(output("print flags"), "flags = ----");
+    assert_eq!(output("delete"), "Deleted 1 breakpoint(s)");
+    assert_eq!(output("reset"), "0x00  LD 0x01");
+    assert_eq!(output("print [0x10]"), "[0x10] = 0x00 (0)");
+
+    assert_eq!(
+        debugger.execute("print [:nowhere]"),
+        Err(DebuggerError::Value(ValueError::UnknownSymbol(
+            ":nowhere".to_owned()
+        )))
+    );
+    assert_eq!(
+        debugger.execute("set acc=256"),
+        Err(DebuggerError::Value(ValueError::Invalid("256".to_owned())))
+    );
+    assert!(matches!(
+        debugger.execute("jump"),
+        Err(DebuggerError::UnknownCommand(_))
+    ));
+    assert_eq!(debugger.execute("quit"), Ok(Reply::Quit));
+}
+
+#[test]
+fn test_parse_value() {
+    let symbols = Symbols::from([("$c".to_owned(), 2)]);
+
+    assert_eq!(parse_value("$c", &symbols), Ok(2));
+    assert_eq!(parse_value(" -1 ", &symbols), Ok(0xFF));
+    assert_eq!(parse_value("0X2A", &symbols), Ok(0x2A));
+    assert_eq!(parse_value("0b10_1010", &symbols), Ok(0x2A));
+    assert_eq!(parse_value("'A'", &symbols), Ok(0x41));
+
+    for invalid in ["--5", "0x+5", "0b+1", "-129", "256", "A", ""] {
+        assert_eq!(
+            parse_value(invalid, &symbols),
+            Err(ValueError::Invalid(invalid.to_owned()))
+        );
+    }
+}
+
+#[test]
+fn test_step_back() {
+    let mut undo = emulator("LD 5\nST [0x10]\nINC ACC\nST [0x10]");
+    undo.set_history_limit(3);
+    undo.run(4);
+    assert_eq!(undo.history().len(), 3);
+
+    let step = undo.step_back().unwrap();
+    assert_eq!((step.pc, undo.pc(), undo.cycles()), (3, 3, 3));
+    assert_eq!(undo.read_ram(0x10), 5);
+    assert_eq!(undo.step_back().map(|step| step.pc), Some(2));
+    assert_eq!(undo.accumulator(), 5);
+    assert_eq!(undo.step_back().map(|step| step.pc), Some(1));
+    assert_eq!(undo.read_ram(0x10), 0);
+    assert_eq!(undo.step_back(), None);
+    assert_eq!(undo.last_step(), None);
+
+    let mut debugger = Debugger::new(emulator(include_str!("../../examples/fibonacci.asm")));
+    debugger.add_symbol(":loop_start", 3);
+    debugger.add_symbol("$c", 2);
+
+    let mut output = |line: &str| match debugger.execute(line) {
+        Ok(Reply::Output(output)) => output,
+        other => panic!("unexpected reply {other:?}"),
+    };
+
+    assert_eq!(
+        output("break :loop_start"),
+        "Breakpoint at 0x03 <:loop_start>"
+    );
+    output("continue");
+    output("continue");
+    assert_eq!(output("step 3"), "0x06  LD [0x01]");
+    assert_eq!(output("step-back 2"), "0x04  ADD [0x01]");
+    assert_eq!(
+        output("reverse-continue"),
+        "Breakpoint at 0x03\n0x03 <:loop_start>  LD [0x00]"
+    );
+    assert_eq!(
+        output("reverse-continue"),
+        "Breakpoint at 0x03\n0x03 <:loop_start>  LD [0x00]"
+    );
+    assert_eq!(output("print [$c]"), "[0x02] <$c> = 0x00 (0)");
+
+    output("continue");
+    assert_eq!(output("print [$c]"), "[0x02] <$c> = 0x02 (2)");
+    assert_eq!(
+        output("last-write [$c]"),
+        "[0x02] 0x00 -> 0x02 at cycle 5\n0x05  ST [$c]"
+    );
+    assert_eq!(output("print [$c]"), "[0x02] <$c> = 0x00 (0)");
+    assert_eq!(
+        output("last-write [0x20]"),
+        "No write to [0x20] in the history"
+    );
+    assert_eq!(
+        output("step-back 100"),
+        "Start of the history, 5 instruction(s) undone\n0x00  LD 0x01"
+    );
+    assert_eq!(
+        output("reverse-continue"),
+        "Start of the history\n0x00  LD 0x01"
+    );
+}
+
+#[test]
+fn test_watchpoints() {
+    let source = "LD 3\n:loop\nST [0x10]\nDEC ACC\nBZ0 :loop\n:end\nBRA :end";
+    let symbols = Symbols::new();
+
+    let condition = Condition::parse("acc == 3 && !(z || [0x10] > 2)", &symbols).unwrap();
+    let mut board = emulator(source);
+    board.tick();
+    assert!(condition.evaluate(&board));
+    board.tick();
+    assert!(!condition.evaluate(&board));
+
+    for (text, expected) in [
+        ("acc == 3", true),
+        ("acc != 3", false),
+        ("acc < 3", false),
+        ("acc <= 3", true),
+        ("acc > 2", true),
+        ("acc >= 4", false),
+    ] {
+        let condition = Condition::parse(text, &symbols).unwrap();
+        assert_eq!(condition.evaluate(&board), expected, "{text}");
+    }
+
+    assert_eq!(
+        WatchKind::parse("[0x10]", &symbols),
+        Ok(WatchKind::Write(0x10))
+    );
+    assert!(matches!(
+        WatchKind::parse("ram[0x10] == 1", &symbols),
+        Ok(WatchKind::Condition(_))
+    ));
+    assert!(matches!(
+        Condition::parse("(acc == 1", &symbols),
+        Err(ConditionError::Syntax(_))
+    ));
+    assert!(matches!(
+        Condition::parse("acc >", &symbols),
+        Err(ConditionError::Syntax(_))
+    ));
+    assert_eq!(
+        Condition::parse("acc == $limit", &symbols),
+        Err(ConditionError::Value(ValueError::UnknownSymbol(
+            "$limit".to_owned()
+        )))
+    );
+
+    let mut debugger = Debugger::new(emulator(source));
+    let mut output = |line: &str| match debugger.execute(line) {
+        Ok(Reply::Output(output)) => output,
+        other => panic!("unexpected reply {other:?}"),
+    };
+
+    assert_eq!(output("watch [0x10]"), "Watchpoint 1 : [0x10] written");
+    assert_eq!(
+        output("continue"),
+        "Watchpoint 1, [0x10] 0x00 -> 0x03\n0x02  DEC ACC"
+    );
+    assert_eq!(
+        output("ignore 1 1"),
+        "Watchpoint 1 will ignore its next 1 hit(s)"
+    );
+    assert_eq!(
+        output("continue"),
+        "Watchpoint 1, [0x10] 0x02 -> 0x01\n0x02  DEC ACC"
+    );
+    assert_eq!(output("watch"), "Watchpoint 1 : [0x10] written, 3 hit(s)");
+    assert_eq!(output("unwatch 1"), "Deleted watchpoint 1");
+    assert_eq!(
+        output("watch acc == 0 && z"),
+        "Watchpoint 2 : acc == 0 && z"
+    );
+    assert_eq!(
+        output("continue"),
+        "Watchpoint 2, acc == 0 && z\n0x03  BZ0 0x01"
+    );
+
+    assert_eq!(
+        debugger.execute("unwatch 1"),
+        Err(DebuggerError::UnknownWatchpoint(1))
+    );
+    assert!(matches!(
+        debugger.execute("watch acc =="),
+        Err(DebuggerError::Condition(ConditionError::Syntax(_)))
+    ));
+}
+
+#[test]
+fn test_debugger_stops_on_unknown_opcode() {
+    let mut debugger = Debugger::new(emulator("NOP\nNOP"));
+
+    let Ok(Reply::Output(output)) = debugger.execute("continue") else {
+        panic!("continue failed");
+    };
+
+    assert_eq!(
+        output,
+        "Stopped, illegal opcode 0x00 at 0x02\n0x02  unknown opcode, word 0x0000"
+    );
+}
+
+#[test]
+fn test_traps() {
+    let mut illegal = emulator(".word 0x2300\nLD 1");
+
+    // Like the hardware, an illegal opcode does nothing
+    assert_eq!(illegal.tick(), StepResult::Ok);
+    assert_eq!(illegal.pc(), 1);
+
+    illegal.reset();
+    illegal.set_trap_policies(TrapPolicies {
+        illegal_opcode: IllegalOpcodePolicy::Stop,
+        ..TrapPolicies::HARDWARE
+    });
+    assert_eq!(
+        illegal.tick(),
+        StepResult::Trap(Trap::IllegalOpcode {
+            pc: 0,
+            word: 0x2300
+        })
+    );
+    assert_eq!(illegal.pc(), 0);
+
+    let mut wrap = emulator("NOP");
+    wrap.set_pc(255);
+    assert_eq!(wrap.tick(), StepResult::Ok);
+    assert_eq!(wrap.pc(), 0);
+
+    wrap.set_pc(255);
+    wrap.set_trap_policies(TrapPolicies {
+        pc_wrap: PcWrapPolicy::Stop,
+        ..TrapPolicies::HARDWARE
+    });
+    assert_eq!(wrap.tick(), StepResult::Trap(Trap::PcWrap));
+    assert_eq!(wrap.pc(), 0);
+
+    let mut halt = emulator("LD 0\n:end\nBZ1 :end");
+    assert_eq!(halt.run(10), StepResult::Ok);
+
+    halt.set_trap_policies(TrapPolicies {
+        halt: HaltPolicy::Stop,
+        ..TrapPolicies::HARDWARE
+    });
+    halt.reset();
+    assert_eq!(halt.run(10), StepResult::Trap(Trap::Halted { pc: 1 }));
+    assert_eq!(halt.pc(), 1);
+}
+
+#[test]
+fn test_run_to_halt() {
+    let mut halt = emulator("LD 5\nST [3]\n:end\nBRA :end");
+    assert_eq!(halt.run_to_halt(100), RunOutcome::Halted { pc: 2 });
+    assert_eq!(halt.cycles(), 3);
+    assert_eq!(halt.read_ram(3), 5);
+
+    // The loop body runs twice so that an unchanged iteration can be seen
+    let mut tight_loop = emulator("LD 0\n:wait\nADD 0\nBZ1 :wait");
+    assert_eq!(tight_loop.run_to_halt(100), RunOutcome::Halted { pc: 1 });
+    assert_eq!(tight_loop.cycles(), 5);
+
+    let mut fibonacci = emulator(include_str!("../../examples/fibonacci.asm"));
+    assert_eq!(fibonacci.run_to_halt(1000), RunOutcome::Timeout);
+    assert_eq!(fibonacci.cycles(), 1000);
+
+    let mut trap = emulator("NOP");
+    trap.set_trap_policies(TrapPolicies::STOP);
+    assert_eq!(
+        trap.run_to_halt(100),
+        RunOutcome::Trap(Trap::IllegalOpcode { pc: 1, word: 0 })
+    );
+    assert_eq!(trap.trap_policies(), TrapPolicies::STOP);
+
+    trap.reset();
+    assert_eq!(trap.cycles(), 0);
+}
+
+#[test]
+fn test_spec_parse() {
+    let spec = TestSpec::parse(
+        "; comment\nprogram multiply.asm\ntest first acc=1 ; note\n  expect acc=2\n\ntest second\n",
+    )
+    .unwrap();
+    assert_eq!(spec.program, "multiply.asm");
+    assert_eq!(
+        spec.cases,
+        [
+            (3, "first acc=1 expect acc=2".to_owned()),
+            (6, "second".to_owned())
+        ]
+    );
+
+    let error = |line: usize, message: &str| SpecError {
+        line,
+        message: message.to_owned(),
+    };
+    assert_eq!(
+        TestSpec::parse("test a\n"),
+        Err(error(1, "no `program <file>` line"))
+    );
+    assert_eq!(
+        TestSpec::parse("program a.o\n  acc=1"),
+        Err(error(2, "indented line outside of a test"))
+    );
+    assert_eq!(
+        TestSpec::parse("program a.o\nexpect acc=1"),
+        Err(error(2, "unknown keyword `expect`"))
+    );
+
+    let symbols = Symbols::from([("$result".to_owned(), 2)]);
+    let case = TestCase::parse(
+        4,
+        "mul ram[1]=3 flags=ZC expect ram[$result]=0x0C n=1 cycles<200",
+        &symbols,
+    )
+    .unwrap();
+    assert_eq!(case.name, "mul");
+    assert_eq!(
+        case.setup,
+        [
+            (Target::Ram(1), 3),
+            (Target::Flag(Flag::Z), 1),
+            (Target::Flag(Flag::C), 1),
+            (Target::Flag(Flag::V), 0),
+            (Target::Flag(Flag::N), 0),
+        ]
+    );
+    assert_eq!(
+        case.expected,
+        [(Target::Ram(2), 12), (Target::Flag(Flag::N), 1)]
+    );
+    assert_eq!(case.max_cycles, 199);
+
+    let case = TestCase::parse(1, "default cycles<=50", &symbols).unwrap();
+    assert_eq!(case.max_cycles, 50);
+    assert_eq!(
+        TestCase::parse(1, "default", &symbols).unwrap().max_cycles,
+        DEFAULT_TEST_CYCLES
+    );
+
+    assert_eq!(
+        TestCase::parse(5, "bad ram[$missing]=1", &symbols),
+        Err(error(
+            5,
+            &ValueError::UnknownSymbol("$missing".to_owned()).to_string()
+        ))
+    );
+    assert_eq!(
+        TestCase::parse(5, "bad z=2", &symbols),
+        Err(error(5, "flag values are 0 or 1, got `2`"))
+    );
+    assert_eq!(
+        TestCase::parse(5, "bad x=1", &symbols),
+        Err(error(5, "unknown target `x`"))
+    );
+}
+
+#[test]
+fn test_case_run() {
+    let multiply = emulator(include_str!("../../examples/multiply.asm"));
+    let symbols = Symbols::from([
+        ("$a".to_owned(), 0),
+        ("$b".to_owned(), 1),
+        ("$result".to_owned(), 2),
+    ]);
+    let case = |text| TestCase::parse(1, text, &symbols).unwrap().run(&multiply);
+
+    let result = case("pass ram[$a]=3 ram[$b]=4 expect ram[$result]=12");
+    assert!(result.passed());
+    assert_eq!(result.to_string(), "PASS pass (37 cycles)");
+
+    let result = case("fail ram[$a]=3 ram[$b]=4 expect ram[$result]=13 acc=0 c=1");
+    assert!(!result.passed());
+    assert_eq!(
+        result.mismatches,
+        [
+            Mismatch {
+                target: Target::Ram(2),
+                expected: 13,
+                actual: 12
+            },
+            Mismatch {
+                target: Target::Flag(Flag::C),
+                expected: 1,
+                actual: 0
+            },
+        ]
+    );
+    assert_eq!(
+        result.to_string(),
+        "FAIL fail (line 1)\n  ram[0x02] : expected 0x0D (13), got 0x0C (12)\n  c : expected 1, got 0"
+    );
+
+    let result = case("slow ram[$a]=3 ram[$b]=4 cycles<10");
+    assert_eq!(result.outcome, RunOutcome::Timeout);
+    assert_eq!(
+        result.to_string(),
+        "FAIL slow (line 1)\n  did not halt within 9 cycles"
+    );
+
+    let result = TestCase::parse(2, "trap", &symbols)
+        .unwrap()
+        .run(&emulator("LD 1"));
+    assert_eq!(
+        result.to_string(),
+        "FAIL trap (line 2)\n  stopped after 1 cycles, illegal opcode 0x00 at 0x01"
+    );
+}
+
+#[test]
+fn test_annotations() {
+    let program = load_program(Path::new("../examples/multiply.asm")).unwrap();
+    assert_eq!(program.tests.len(), 2);
+
+    for (line, text) in &program.tests {
+        let result = TestCase::parse(*line, text, &program.symbols)
+            .unwrap()
+            .run(&program.emulator);
+        assert!(result.passed(), "{result}");
+    }
+}
+
+#[test]
+fn test_step_record() {
+    let mut emulator = emulator("LD 0x80\nST [3]\nBRA 0");
+    assert_eq!(emulator.last_step(), None);
+
+    emulator.run(2);
+    assert_eq!(
+        emulator.last_step(),
+        Some(&StepRecord {
+            cycle: 1,
+            pc: 1,
+            word: 0x0103,
+            accumulator_before: 0x80,
+            accumulator_after: 0x80,
+            flags_before: Flags::new(false, false, false, true),
+            flags_after: Flags::new(false, false, false, true),
+            ram_write: Some(RamWrite {
+                address: 3,
+                old_value: 0,
+                new_value: 0x80
+            }),
+        })
+    );
+
+    emulator.reset();
+    assert_eq!(emulator.last_step(), None);
+}
+
+#[test]
+fn test_debug_info() {
+    let source_path = Path::new("../examples/multiply.asm");
+    let source = std::fs::read_to_string(source_path).unwrap();
+    let program = nano_chip_assembler::assemble(&source).unwrap();
+
+    let directory = std::env::temp_dir().join(format!("nano_chip_debug_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let binary = directory.join("multiply.o");
+    std::fs::write(&binary, program.to_bytes()).unwrap();
+
+    // Without debug info, a binary has no names
+    assert!(load_program(&binary).unwrap().symbols.is_empty());
+
+    let source_path = std::fs::canonicalize(source_path).unwrap();
+    let debug_info = program.debug_info(&source_path.display().to_string());
+    std::fs::write(directory.join("multiply.dbg"), debug_info.to_string()).unwrap();
+    let loaded = load_program(&binary).unwrap();
+
+    // Debug info left from another build of the binary is rejected
+    let mut rebuilt = program.to_bytes();
+    rebuilt.extend([0x3F, 0x00]);
+    std::fs::write(&binary, rebuilt).unwrap();
+    let stale = load_program(&binary);
+
+    std::fs::write(&binary, program.to_bytes()).unwrap();
+    std::fs::write(
+        directory.join("multiply.dbg"),
+        "nano_chip_debug 1\nline 0x01 2",
+    )
+    .unwrap();
+    let broken = load_program(&binary);
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(loaded.symbols.get(":loop_start"), Some(&2));
+    assert_eq!(
+        loaded.source_lines[2],
+        (12, "LD [$b]   ; acc = b".to_owned())
+    );
+    assert_eq!(loaded.source_path, Some(source_path.display().to_string()));
+    assert!(matches!(broken, Err(LoadError::DebugInfo { .. })));
+    assert!(matches!(
+        stale,
+        Err(LoadError::DebugInfoMismatch {
+            expected: (12, _),
+            found: (11, _),
+            ..
+        })
+    ));
+
+    let mut debugger = Debugger::new(loaded.emulator);
+    debugger.add_symbols(&loaded.symbols);
+    debugger.set_source("multiply.asm", vec![8, 9, 12, 13]);
+
+    let mut output = |line: &str| match debugger.execute(line) {
+        Ok(Reply::Output(output)) => output,
+        other => panic!("unexpected reply {other:?}"),
+    };
+
+    assert_eq!(
+        output("break :loop_start"),
+        "Breakpoint at 0x02 <:loop_start>"
+    );
+    assert_eq!(output("set [$b]=1"), "");
+    assert_eq!(
+        output("continue"),
+        "Breakpoint at 0x02\n0x02 <:loop_start>  LD [$b]  (multiply.asm:12)"
+    );
+    assert_eq!(output("step"), "0x03  BZ1 :end  (multiply.asm:13)");
+    assert_eq!(output("step"), "0x04  DEC ACC");
+    assert_eq!(
+        output("mem 0..4"),
+        "0x00: 00 01 00 00\n[0x00] <$a> = 0x00 (0)\n[0x01] <$b> = 0x01 (1)\n[0x02] <$result> = 0x00 (0)"
+    );
+
+    let mut trace = TraceWriter::new(TraceFormat::Text, Vec::new()).unwrap();
+    trace.set_symbols(&loaded.symbols);
+    let mut multiply = emulator(&source);
+    multiply.write_ram(1, 1);
+    multiply.run_to_halt_with(100, |step| trace.write(step).unwrap());
+    let trace = String::from_utf8(trace.into_inner()).unwrap();
+    assert!(trace.contains("  BZ1 :end      "));
+    assert!(
+        trace.contains("  ST [$b]       acc 00 -> 00  flags Z--- -> Z---  [0x01] $b 01 -> 00\n")
+    );
+}
+
+#[test]
+fn test_trace() {
+    let trace = |format| {
+        let mut emulator = emulator("LD 5\nST [0x10]\n.word 0x2300\n:end\nBRA :end");
+        emulator.set_trap_policies(TrapPolicies::HARDWARE);
+        let mut trace = TraceWriter::new(format, Vec::new()).unwrap();
+        emulator.run_to_halt_with(10, |step| trace.write(step).unwrap());
+        String::from_utf8(trace.into_inner()).unwrap()
+    };
+
+    assert_eq!(
+        trace(TraceFormat::Text),
+        "       0  0x00  0205  LD 0x05       acc 00 -> 05  flags ---- -> ----
+       1  0x01  0110  ST [0x10]     acc 05 -> 05  flags ---- -> ----  [0x10] 00 -> 05
+       2  0x02  2300  illegal 0x2300  acc 05 -> 05  flags ---- -> ----
+       3  0x03  2203  BRA 0x03      acc 05 -> 05  flags ---- -> ----
+"
+    );
+
+    let csv = trace(TraceFormat::Csv);
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("cycle,pc,word,mnemonic,operand,acc_before,acc_after,z_before,c_before,v_before,n_before,z_after,c_after,v_after,n_after,ram_address,ram_old,ram_new")
+    );
+    assert_eq!(
+        lines.nth(1),
+        Some("1,1,272,ST,[0x10],5,5,0,0,0,0,0,0,0,0,16,0,5")
+    );
+    assert_eq!(lines.next(), Some("2,2,8960,,,5,5,0,0,0,0,0,0,0,0,,,"));
+
+    let json = trace(TraceFormat::JsonLines);
+    assert_eq!(
+        json.lines().next(),
+        Some("{\"cycle\":0,\"pc\":0,\"word\":517,\"mnemonic\":\"LD\",\"operand\":\"0x05\",\"acc_before\":0,\"acc_after\":5,\"z_before\":0,\"c_before\":0,\"v_before\":0,\"n_before\":0,\"z_after\":0,\"c_after\":0,\"v_after\":0,\"n_after\":0,\"ram_address\":null,\"ram_old\":null,\"ram_new\":null}")
+    );
+    assert_eq!(json.lines().count(), 4);
+}
+
+#[test]
+fn test_vcd() {
+    let mut emulator = emulator("LD 5\nST [0x10]\n:end\nBRA :end");
+    let mut config = VcdConfig {
+        clock_period: 10,
+        ..VcdConfig::default()
+    };
+    assert!(config.rename("pc", "pc_reg"));
+    assert!(config.rename("Z", "zero"));
+    assert!(!config.rename("missing", "name"));
+    config.add_ram_cell(0x10, None);
+
+    let mut vcd = VcdWriter::new(config, Vec::new(), &emulator).unwrap();
+    emulator.run_to_halt_with(10, |step| vcd.write(step).unwrap());
+    let vcd = String::from_utf8(vcd.finish().unwrap()).unwrap();
+
+    assert_eq!(
+        vcd,
+        "$version nano_chip_emulator $end
+$timescale 1ns $end
+$scope module nano_chip $end
+$var wire 1 ! clk $end
+$var wire 8 \" pc_reg $end
+$var wire 8 # accumulator $end
+$var wire 1 $ zero $end
+$var wire 1 % c $end
+$var wire 1 & v $end
+$var wire 1 ' n $end
+$var wire 14 ( instruction $end
+$var wire 8 ) ram_10 $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b00000000 \"
+b00000000 #
+0$
+0%
+0&
+0'
+b00001000000101 (
+b00000000 )
+$end
+1!
+#5
+0!
+#10
+b00000101 #
+1!
+b00000001 \"
+b00000100010000 (
+#15
+0!
+#20
+b00000101 )
+1!
+b00000010 \"
+b10001000000010 (
+#25
+0!
+#30
+"
+    );
+}
+
+#[test]
+fn test_bus() {
+    let mut bus = Bus::new();
+    bus.map(0xF0, Box::new(Leds::new())).unwrap();
+    bus.map(0xF2, Box::new(SevenSegment::new(2))).unwrap();
+    assert_eq!(
+        bus.map(0xF3, Box::new(Console::new())),
+        Err(BusError::Overlap(0xF2))
+    );
+    assert_eq!(
+        bus.map(0xFF, Box::new(SevenSegment::new(2))),
+        Err(BusError::OutOfRange(0xFF))
+    );
+
+    bus.write(0xF0, 0b1000_0001);
+    bus.write(0xF2, SevenSegment::encode(0xA));
+    bus.write(0xF3, SevenSegment::encode(7) | 0x80);
+    bus.write(0xF4, 9);
+    assert_eq!(bus.read(0xF0), 0b1000_0001);
+    assert_eq!(bus.read(0xF4), 9);
+    assert_eq!(bus.ram()[0xF0], 0);
+    assert_eq!(
+        bus.device_states(),
+        "0xF0  leds           ●○○○○○○●\n0xF2  seven_segment  A7."
+    );
+
+    let leds = bus.device_at(0xF0).unwrap().device.as_any();
+    assert_eq!(
+        leds.downcast_ref::<Leds>().map(Leds::value),
+        Some(0b1000_0001)
+    );
+
+    bus.reset();
+    assert_eq!(bus.read(0xF0), 0);
+    assert_eq!(bus.read(0xF4), 0);
+    assert_eq!(bus.mappings().len(), 2);
+}
+
+#[test]
+fn test_memory_map() {
+    let mut emulator = emulator("LD [0xF1]\nST [0xF0]\n:loop\nLD 'A'\nST [0xFF]\nBRA :loop");
+    let symbols = Symbols::from([("$console".to_owned(), 0xFF)]);
+
+    load_memory_map(
+        "; board\nleds 0xF0\nswitches 0xF1 0x5A\n\nconsole $console ; output",
+        &symbols,
+        emulator.bus_mut(),
+    )
+    .unwrap();
+
+    // Writing to a device is never an idle loop
+    assert_eq!(emulator.run_to_halt(11), RunOutcome::Timeout);
+    assert_eq!(emulator.read_ram(0xF0), 0x5A);
+
+    let console = emulator.bus().device_at(0xFF).unwrap().device.as_any();
+    assert_eq!(
+        console.downcast_ref::<Console>().map(Console::output),
+        Some("AAA")
+    );
+
+    let switches = &mut emulator.bus_mut().mappings_mut()[1].device;
+    switches
+        .as_any_mut()
+        .downcast_mut::<Switches>()
+        .unwrap()
+        .set(3);
+    assert_eq!(emulator.read_ram(0xF1), 3);
+
+    emulator.reset();
+    assert_eq!(emulator.read_ram(0xF1), 0x5A);
+    assert_eq!(emulator.bus().device_states().lines().count(), 3);
+
+    let error = |text| load_memory_map(text, &symbols, &mut Bus::new()).unwrap_err();
+    let message = |line, message: &str| MemoryMapError {
+        line,
+        message: message.to_owned(),
+    };
+
+    assert_eq!(
+        error("leds"),
+        message(1, "expected a device and its address")
+    );
+    assert_eq!(
+        error("\nlamp 0x10"),
+        message(
+            2,
+            "unknown device `lamp`, expected leds, switches, seven_segment or console"
+        )
+    );
+    assert_eq!(error("leds 0x10 2"), message(1, "leds takes no parameter"));
+    assert_eq!(
+        error("seven_segment 0x10 0"),
+        message(1, "a 7-segment display needs at least one digit")
+    );
+    assert_eq!(
+        error("leds 0x10\nconsole 0x10"),
+        message(2, "overlaps the device at 0x10")
+    );
+}
+
+#[test]
+fn test_coverage() {
+    let mut program = load_program(Path::new("../examples/multiply.asm")).unwrap();
+    program.emulator.write_ram(0, 3);
+    program.emulator.write_ram(1, 1);
+
+    let mut coverage = Coverage::new();
+    program
+        .emulator
+        .run_to_halt_with(100, |step| coverage.record(step));
+
+    assert_eq!(coverage.executed(2), 2);
+    assert_eq!(coverage.branch(3), (1, 1));
+
+    let rom = program.emulator.rom();
+    let listing = coverage.listing(rom, &program.source_lines, &program.symbols);
+    assert!(listing.contains(":loop_start\n       2  0x02                          12  LD [$b]"));
+    assert!(listing.contains("       2  0x03  both 1/1                13  BZ1 :end"));
+
+    // Without the source, the program ends at the last word that isn't 0
+    let listing = coverage.listing(rom, &[], &Symbols::new());
+    assert!(listing.ends_with("       1  0x0A                              BRA 0x0A\n"));
+
+    let summary = coverage.summary(rom, &program.source_lines, &program.symbols);
+    assert!(summary.contains("\n:loop_start  8/8        100%     2/2        100%\n"));
+    assert!(summary.ends_with("\nTotal        11/11      100%     2/2        100%\n"));
+
+    let lcov = coverage.lcov(rom, &program.source_lines, &program.symbols, "multiply.asm");
+    assert!(lcov.starts_with("TN:\nSF:multiply.asm\nFN:12,loop_start\n"));
+    assert!(lcov.contains("\nBRDA:13,0,0,1\nBRDA:13,0,1,1\nBRF:2\nBRH:2\n"));
+    assert!(lcov.ends_with("\nDA:23,1\nLF:11\nLH:11\nend_of_record\n"));
+}
+
+#[test]
+fn test_profile() {
+    let mut program = load_program(Path::new("../examples/multiply.asm")).unwrap();
+    program.emulator.write_ram(0, 3);
+    program.emulator.write_ram(1, 1);
+
+    let mut profile = Profile::new();
+    program
+        .emulator
+        .run_to_halt_with(100, |step| profile.record(step));
+
+    assert_eq!(profile.cycles(), 13);
+    assert_eq!(profile.executed(2), 2);
+    assert_eq!(profile.ram_accesses(1), (2, 1));
+    assert_eq!(profile.ram_accesses(2), (1, 2));
+
+    let rom = program.emulator.rom();
+    let report = profile.report(rom, &program.symbols);
+    assert!(report.starts_with("Total cycles : 13\n"));
+    assert!(report.contains("\n       2   15.4%  0x02  :loop_start   LD [0x01]\n"));
+    assert!(report.contains("\n      10   76.9%  :loop_start\n       2   15.4%  (start)\n"));
+    assert!(report.contains("\n       3   23.1%  LD [addr]\n"));
+    assert!(report.ends_with("\n0x01         2         1\n0x02         1         2\n"));
+
+    let folded = profile.folded(rom, &program.symbols);
+    assert!(folded.starts_with("(start);0x00 LD 0x00 1\n(start);0x01 ST [0x02] 1\n"));
+    assert!(folded.contains("\nloop_start;0x03 BZ1 0x0A 2\n"));
+    assert!(folded.ends_with("\nend;0x0A BRA 0x0A 1\n"));
+}
+
+#[test]
+fn test_snapshot() {
+    let source = "LD 7\nST [0x20]\n:loop\nLD 'A'\nST [0xFF]\nBRA :loop";
+    let mut board = emulator(source);
+    load_memory_map("leds 0x20\nconsole 0xFF", &Symbols::new(), board.bus_mut()).unwrap();
+    board.write_ram(0x10, 0x42);
+    board.run(7);
+
+    let snapshot = board.snapshot();
+    let text = snapshot.to_string();
+    assert!(text.starts_with("nano_chip_snapshot 1\n"));
+    assert!(text.contains("\nacc 0x41\nflags ----\ncycles 7\n"));
+    assert!(text.ends_with("device 0x20 leds 07\ndevice 0xFF console 4141\n"));
+    assert_eq!(Snapshot::parse(&text).unwrap(), snapshot);
+
+    let mut restored = board.clone();
+    restored.reset();
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.pc(), board.pc());
+    assert_eq!(restored.cycles(), 7);
+    assert_eq!(restored.read_ram(0x10), 0x42);
+    assert_eq!(restored.bus().device_states(), board.bus().device_states());
+
+    assert!(matches!(
+        board.clone().restore(&emulator(source).snapshot()),
+        Err(SnapshotError::Devices(_))
+    ));
+    assert!(matches!(
+        emulator(&format!("{source}\nNOP")).restore(&snapshot),
+        Err(SnapshotError::RomMismatch { .. })
+    ));
+    assert!(matches!(
+        Snapshot::parse("; saved\nnano_chip_snapshot 2\n"),
+        Err(SnapshotError::Version(2))
+    ));
+    assert!(matches!(
+        Snapshot::parse(&text.replace("ram 0x30", "ram 0x31")),
+        Err(SnapshotError::Parse { line: 10, .. })
+    ));
+}
+
+#[test]
+fn test_tui() {
+    assert_eq!(
+        Key::parse(b"s\x1b[A:\x7f\r\x1b"),
+        [
+            Key::Char('s'),
+            Key::Other,
+            Key::Char(':'),
+            Key::Backspace,
+            Key::Enter,
+            Key::Escape
+        ]
+    );
+
+    let mut debugger = Debugger::new(emulator("LD 3\nST [0x12]\n:loop\nINC ACC\nBRA :loop"));
+    debugger.add_symbol(":loop", 2);
+    let mut tui = Tui::new(debugger, "loop.asm");
+
+    let screen = tui.render(24);
+    assert_eq!(screen.lines().count(), 24);
+    assert!(screen.contains("\x1b[7m > 0x00"));
+    assert!(screen.contains("0x02  :loop"));
+
+    tui.handle_key(Key::Char('s'));
+    tui.handle_key(Key::Char('s'));
+    let screen = tui.render(24);
+    assert!(screen.contains("ACC     0x03 (3)"));
+    assert!(screen.contains("0x10  00 00 \x1b[1;33m03\x1b[0m 00"));
+
+    // The breakpoint reached by the last instruction of a frame stops the run at the next frame
+    tui.handle_key(Key::Char('b'));
+    assert!(tui.render(24).contains("*> 0x02"));
+    tui.handle_key(Key::Char('r'));
+    assert!(tui.running());
+    tui.tick(Duration::from_millis(100));
+    assert!(tui.running());
+    tui.tick(Duration::from_secs(1));
+    assert!(!tui.running());
+    assert_eq!(tui.debugger().emulator().pc(), 2);
+    assert_eq!(tui.debugger().emulator().accumulator(), 4);
+
+    tui.handle_key(Key::Char('+'));
+    assert_eq!(tui.speed(), DEFAULT_SPEED * 2);
+
+    for key in Key::parse(b":print [0x12]\r") {
+        tui.handle_key(key);
+    }
+    assert!(tui.render(24).contains("[0x12] = 0x03 (3)"));
+
+    tui.handle_key(Key::Char('x'));
+    assert_eq!(tui.debugger().emulator().pc(), 0);
+    assert!(!tui.quit_requested());
+    tui.handle_key(Key::Char('q'));
+    assert!(tui.quit_requested());
+}
+
+/// Send a packet as GDB does and wait for the stub to acknowledge it
+fn gdb_send(stream: &mut TcpStream, payload: &str) {
+    let checksum = payload.bytes().fold(0_u8, u8::wrapping_add);
+    write!(stream, "${payload}#{checksum:02x}").unwrap();
+
+    let mut ack = [0];
+    stream.read_exact(&mut ack).unwrap();
+    assert_eq!(ack, *b"+");
+}
+
+/// Read the next reply, check its checksum and acknowledge it
+fn gdb_receive(stream: &mut TcpStream) -> String {
+    let mut next_byte = || {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    };
+    assert_eq!(next_byte(), b'$');
+
+    let mut payload = Vec::new();
+
+    loop {
+        match next_byte() {
+            b'#' => break,
+            byte => payload.push(byte),
+        }
+    }
+
+    let checksum = String::from_utf8(vec![next_byte(), next_byte()]).unwrap();
+    assert_eq!(
+        u8::from_str_radix(&checksum, 16).unwrap(),
+        payload
+            .iter()
+            .fold(0_u8, |sum, byte| sum.wrapping_add(*byte))
+    );
+
+    stream.write_all(b"+").unwrap();
+    String::from_utf8(payload).unwrap()
+}
+
+fn gdb_exchange(stream: &mut TcpStream, payload: &str) -> String {
+    gdb_send(stream, payload);
+    gdb_receive(stream)
+}
+
+/// GDB session scripted against the program of `test_gdb_stub`
+fn gdb_client(address: SocketAddr, rom: &[u16; ROM_SIZE]) {
+    let mut client = TcpStream::connect(address).unwrap();
+    client.set_nodelay(true).unwrap();
+
+    let supported = gdb_exchange(&mut client, "qSupported:multiprocess+;swbreak+");
+    assert!(supported.contains("qXfer:features:read+"));
+    assert_eq!(
+        gdb_exchange(&mut client, "qXfer:features:read:target.xml:0,5"),
+        "m<?xml"
+    );
+    assert_eq!(
+        gdb_exchange(&mut client, "qXfer:features:read:target.xml:0,fff"),
+        format!("l{TARGET_XML}")
+    );
+    assert!(TARGET_XML.contains(r#"<reg name="acc" bitsize="8""#));
+
+    // A packet with a wrong checksum is rejected, GDB sends it again
+    client.write_all(b"$g#00").unwrap();
+    let mut nack = [0];
+    client.read_exact(&mut nack).unwrap();
+    assert_eq!(nack, *b"-");
+
+    assert_eq!(gdb_exchange(&mut client, "?"), "S05");
+    assert_eq!(gdb_exchange(&mut client, "g"), "000000");
+    assert_eq!(
+        gdb_exchange(&mut client, "m10000,4"),
+        format!("{:04X}{:04X}", rom[0], rom[1])
+    );
+    assert_eq!(gdb_exchange(&mut client, "m20000,1"), "E01");
+
+    assert_eq!(gdb_exchange(&mut client, "Z0,2,2"), "OK");
+    assert_eq!(gdb_exchange(&mut client, "Z2,10,1"), "");
+    assert_eq!(gdb_exchange(&mut client, "c"), "S05");
+    assert_eq!(gdb_exchange(&mut client, "g"), "020300");
+    assert_eq!(gdb_exchange(&mut client, "m10,2"), "0300");
+
+    assert_eq!(gdb_exchange(&mut client, "M11,1:2a"), "OK");
+    assert_eq!(gdb_exchange(&mut client, "m11,1"), "2A");
+    assert_eq!(gdb_exchange(&mut client, "M10000,1:00"), "E01");
+    assert_eq!(gdb_exchange(&mut client, "Mffffffffffffffff,1:00"), "E01");
+    assert_eq!(gdb_exchange(&mut client, "M11,1:+F"), "E01");
+
+    assert_eq!(gdb_exchange(&mut client, "s"), "S05");
+    assert_eq!(gdb_exchange(&mut client, "p1"), "04");
+    assert_eq!(gdb_exchange(&mut client, "P1=fe"), "OK");
+    assert_eq!(gdb_exchange(&mut client, "p1"), "FE");
+
+    // The branch leads back to the breakpoint
+    assert_eq!(gdb_exchange(&mut client, "c"), "S05");
+    assert_eq!(gdb_exchange(&mut client, "g"), "02FE00");
+
+    assert_eq!(gdb_exchange(&mut client, "z0,2,2"), "OK");
+    assert_eq!(gdb_exchange(&mut client, "G03fe01"), "OK");
+    assert_eq!(gdb_exchange(&mut client, "g"), "03FE01");
+
+    // Without breakpoint the loop runs until GDB interrupts it
+    gdb_send(&mut client, "c");
+    client.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb_receive(&mut client), "S02");
+
+    // A packet sent while the program runs is answered after the stop reply
+    gdb_send(&mut client, "c");
+    client.write_all(b"+$qAttached#8f").unwrap();
+    client.write_all(&[0x03]).unwrap();
+    assert_eq!(gdb_receive(&mut client), "S02");
+    let mut ack = [0];
+    client.read_exact(&mut ack).unwrap();
+    assert_eq!(ack, *b"+");
+    assert_eq!(gdb_receive(&mut client), "1");
+
+    assert_eq!(gdb_exchange(&mut client, "vMustReplyEmpty"), "");
+    assert_eq!(gdb_exchange(&mut client, "D"), "OK");
+}
+
+#[test]
+fn test_gdb_stub() {
+    let source = "LD 3\nST [0x10]\n:loop\nINC ACC\nBRA :loop";
+    let mut stub = GdbStub::new(Debugger::new(emulator(source)));
+    let rom = *stub.debugger().emulator().rom();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let client = std::thread::spawn(move || gdb_client(address, &rom));
+
+    let (mut stream, _) = listener.accept().unwrap();
+    stub.serve(&mut stream).unwrap();
+    client.join().unwrap();
+
+    assert!(stub.finished());
+    assert_eq!(stub.debugger().emulator().read_ram(0x11), 0x2A);
+}
@@ -0,0 +1,190 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use nano_chip_isa::{Flag, Flags};
+
+use crate::value::{constant_at, format_instruction, Symbols};
+use crate::{RamWrite, StepRecord};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One aligned line per instruction, for reading
+    Text,
+    /// Comma separated values with a header line
+    Csv,
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl TraceFormat {
+    /// Format from its command line name : `text`, `csv` or `jsonl`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "csv" => Some(Self::Csv),
+            "jsonl" => Some(Self::JsonLines),
+            _ => None,
+        }
+    }
+}
+
+/// Value of a CSV column or JSON member
+enum Field {
+    Number(u64),
+    Text(String),
+    Missing,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(number) => write!(f, "{number}"),
+            Self::Text(text) => write!(f, "{text}"),
+            Self::Missing => Ok(()),
+        }
+    }
+}
+
+const COLUMNS: [&str; 18] = [
+    "cycle",
+    "pc",
+    "word",
+    "mnemonic",
+    "operand",
+    "acc_before",
+    "acc_after",
+    "z_before",
+    "c_before",
+    "v_before",
+    "n_before",
+    "z_after",
+    "c_after",
+    "v_after",
+    "n_after",
+    "ram_address",
+    "ram_old",
+    "ram_new",
+];
+
+/// Values of the record in the order of `COLUMNS`, numbers are decimal and the mnemonic of an illegal opcode is missing
+fn fields(step: &StepRecord) -> [Field; COLUMNS.len()] {
+    let decoded = nano_chip_isa::decode(step.word);
+    let flag = |flags: Flags, flag| Field::Number(u64::from(flags.get(flag)));
+    let ram = |value: fn(&RamWrite) -> u8| {
+        step.ram_write.as_ref().map_or(Field::Missing, |write| {
+            Field::Number(u64::from(value(write)))
+        })
+    };
+
+    [
+        Field::Number(step.cycle),
+        Field::Number(u64::from(step.pc)),
+        Field::Number(u64::from(step.word)),
+        decoded.map_or(Field::Missing, |(info, _)| {
+            Field::Text(info.mnemonic.name().to_owned())
+        }),
+        decoded.map_or(Field::Missing, |(info, operand)| {
+            Field::Text(info.format_operand(operand))
+        }),
+        Field::Number(u64::from(step.accumulator_before)),
+        Field::Number(u64::from(step.accumulator_after)),
+        flag(step.flags_before, Flag::Z),
+        flag(step.flags_before, Flag::C),
+        flag(step.flags_before, Flag::V),
+        flag(step.flags_before, Flag::N),
+        flag(step.flags_after, Flag::Z),
+        flag(step.flags_after, Flag::C),
+        flag(step.flags_after, Flag::V),
+        flag(step.flags_after, Flag::N),
+        ram(|write| write.address),
+        ram(|write| write.old_value),
+        ram(|write| write.new_value),
+    ]
+}
+
+/// Human readable line, like `   12  0x05  0D01  ADD [$a]  acc 03 -> 07  flags ---- -> ----`
+/// followed by the RAM write if any, like `[0x02] $result 00 -> 07`
+fn text_line(step: &StepRecord, symbols: &Symbols) -> String {
+    let instruction = format_instruction(step.word, symbols)
+        .unwrap_or_else(|| format!("illegal 0x{:04X}", step.word));
+
+    let ram_write = step.ram_write.map_or_else(String::new, |write| {
+        let name = constant_at(symbols, write.address)
+            .map_or_else(String::new, |constant| format!(" {constant}"));
+
+        format!(
+            "  [0x{:02X}]{name} {:02X} -> {:02X}",
+            write.address, write.old_value, write.new_value
+        )
+    });
+
+    format!(
+        "{:>8}  0x{:02X}  {:04X}  {instruction:<12}  acc {:02X} -> {:02X}  flags {} -> {}{ram_write}",
+        step.cycle,
+        step.pc,
+        step.word,
+        step.accumulator_before,
+        step.accumulator_after,
+        step.flags_before.letters(),
+        step.flags_after.letters()
+    )
+}
+
+/// Writes one record per executed instruction
+pub struct TraceWriter<W: Write> {
+    format: TraceFormat,
+    output: W,
+    /// Names used by the text format
+    symbols: Symbols,
+}
+
+impl<W: Write> TraceWriter<W> {
+    /// The CSV header is written right away
+    pub fn new(format: TraceFormat, mut output: W) -> io::Result<Self> {
+        if format == TraceFormat::Csv {
+            writeln!(output, "{}", COLUMNS.join(","))?;
+        }
+
+        Ok(Self {
+            format,
+            output,
+            symbols: Symbols::new(),
+        })
+    }
+
+    /// Name branch targets and RAM addresses in the text format, CSV and JSON keep the numbers
+    pub fn set_symbols(&mut self, symbols: &Symbols) {
+        symbols.clone_into(&mut self.symbols);
+    }
+
+    pub fn write(&mut self, step: &StepRecord) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.output, "{}", text_line(step, &self.symbols)),
+            TraceFormat::Csv => {
+                let values = fields(step).map(|field| field.to_string());
+                writeln!(self.output, "{}", values.join(","))
+            }
+            TraceFormat::JsonLines => {
+                let members = COLUMNS
+                    .iter()
+                    .zip(fields(step))
+                    .map(|(name, field)| match field {
+                        Field::Number(number) => format!("\"{name}\":{number}"),
+                        Field::Text(text) => format!("\"{name}\":\"{text}\""),
+                        Field::Missing => format!("\"{name}\":null"),
+                    })
+                    .collect::<Vec<_>>();
+
+                writeln!(self.output, "{{{}}}", members.join(","))
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
@@ -0,0 +1,373 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use nano_chip_isa::{RAM_SIZE, ROM_SIZE};
+
+use crate::{Debugger, Reply, Stop};
+
+/// Instructions per second when the TUI starts
+pub const DEFAULT_SPEED: u64 = 10;
+
+/// Fastest speed reachable with `+`, in instructions per second
+pub const MAX_SPEED: u64 = 1 << 20;
+
+const DISASSEMBLY_ROWS: usize = 10;
+const RAM_ROWS: usize = 8;
+const LEFT_WIDTH: usize = 44;
+
+const KEYS: &str =
+    "s step  r run  p pause  x reset  +/- speed  b breakpoint  [/] RAM  : command  q quit";
+
+const HIGHLIGHT: &str = "\x1b[7m";
+const CHANGED: &str = "\x1b[1;33m";
+const NORMAL: &str = "\x1b[0m";
+
+/// Key press decoded from the terminal input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Escape,
+    /// Arrows, function keys and other sequences the TUI doesn't use
+    Other,
+}
+
+impl Key {
+    /// Decode the bytes read from a terminal without line buffering
+    pub fn parse(bytes: &[u8]) -> Vec<Self> {
+        let mut keys = Vec::new();
+        let mut bytes = bytes.iter().copied();
+
+        while let Some(byte) = bytes.next() {
+            keys.push(match byte {
+                b'\r' | b'\n' => Self::Enter,
+                0x08 | 0x7F => Self::Backspace,
+                0x1B => match bytes.clone().next() {
+                    // Control sequence, like the arrows, ends with a byte from `@` to `~`
+                    Some(b'[') => {
+                        bytes.next();
+                        bytes.find(|byte| (b'@'..=b'~').contains(byte));
+                        Self::Other
+                    }
+                    _ => Self::Escape,
+                },
+                b' '..=b'~' => Self::Char(char::from(byte)),
+                _ => Self::Other,
+            });
+        }
+
+        keys
+    }
+}
+
+/// State of the full screen front end : the debugger, the run state and what is shown
+pub struct Tui {
+    debugger: Debugger,
+    /// Shown in the title bar, usually the program file name
+    title: String,
+    running: bool,
+    /// Instructions per second while running
+    speed: u64,
+    /// Time not yet spent on instructions, in nanoseconds times the speed
+    pending: u128,
+    /// Memory seen by the CPU before the last executed instructions, to highlight changed cells
+    previous_ram: [u8; RAM_SIZE],
+    /// First address of the RAM view
+    ram_start: usize,
+    /// Text being typed after `:`
+    command: Option<String>,
+    /// Reply of the last command or reason of the last stop
+    message: String,
+    quit: bool,
+}
+
+impl Tui {
+    pub fn new(debugger: Debugger, title: &str) -> Self {
+        let mut tui = Self {
+            debugger,
+            title: title.to_owned(),
+            running: false,
+            speed: DEFAULT_SPEED,
+            pending: 0,
+            previous_ram: [0; RAM_SIZE],
+            ram_start: 0,
+            command: None,
+            message: "Type : for debugger commands, :help lists them".to_owned(),
+            quit: false,
+        };
+
+        tui.previous_ram = tui.memory();
+        tui
+    }
+
+    pub const fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub const fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub const fn running(&self) -> bool {
+        self.running
+    }
+
+    pub const fn speed(&self) -> u64 {
+        self.speed
+    }
+
+    pub const fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    /// Memory as seen by the CPU, devices included
+    fn memory(&self) -> [u8; RAM_SIZE] {
+        let emulator = self.debugger.emulator();
+        std::array::from_fn(|address| emulator.read_ram(u8::try_from(address).unwrap_or_default()))
+    }
+
+    /// Start a new highlight of the changed cells
+    fn remember_memory(&mut self) {
+        self.previous_ram = self.memory();
+    }
+
+    /// Execute instructions and stop running on breakpoints and traps
+    ///
+    /// When `resume` is false a breakpoint on the current instruction stops before executing it
+    fn execute(&mut self, count: u64, resume: bool) {
+        self.remember_memory();
+
+        let pc = self.debugger.emulator().pc();
+        let stop = if !resume && self.debugger.breakpoints().contains(&pc) {
+            Err(Stop::Breakpoint)
+        } else {
+            self.debugger.run(count)
+        };
+
+        match stop {
+            Ok(()) => {}
+            Err(Stop::Breakpoint) => {
+                self.running = false;
+                self.message = format!("Breakpoint at 0x{:02X}", self.debugger.emulator().pc());
+            }
+            Err(Stop::Watchpoint(id)) => {
+                self.running = false;
+                self.message = self.debugger.watchpoint_hit(id);
+            }
+            Err(Stop::Trap(trap)) => {
+                self.running = false;
+                self.message = format!("Stopped, {trap}");
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: Key) {
+        if let Some(command) = &mut self.command {
+            match key {
+                Key::Char(character) => command.push(character),
+                Key::Backspace => {
+                    command.pop();
+                }
+                Key::Escape => self.command = None,
+                Key::Enter => {
+                    let command = self.command.take().unwrap_or_default();
+                    self.run_command(&command);
+                }
+                Key::Other => {}
+            }
+
+            return;
+        }
+
+        let Key::Char(character) = key else {
+            return;
+        };
+
+        match character {
+            's' => {
+                self.running = false;
+                self.message.clear();
+                self.execute(1, true);
+            }
+            'r' if !self.running => {
+                self.running = true;
+                self.pending = 0;
+                self.message.clear();
+                self.execute(1, true);
+            }
+            'p' => self.running = false,
+            'x' => {
+                self.running = false;
+                self.remember_memory();
+                self.debugger.emulator_mut().reset();
+                "Reset".clone_into(&mut self.message);
+            }
+            '+' => self.speed = (self.speed * 2).min(MAX_SPEED),
+            '-' => self.speed = (self.speed / 2).max(1),
+            'b' => {
+                let pc = self.debugger.emulator().pc();
+                self.message = if self.debugger.toggle_breakpoint(pc) {
+                    format!("Breakpoint at 0x{pc:02X}")
+                } else {
+                    format!("Deleted breakpoint at 0x{pc:02X}")
+                };
+            }
+            '[' => self.ram_start = self.ram_start.saturating_sub(16),
+            ']' => self.ram_start = (self.ram_start + 16).min(RAM_SIZE - RAM_ROWS * 16),
+            ':' => self.command = Some(String::new()),
+            'q' => self.quit = true,
+            _ => {}
+        }
+    }
+
+    /// Run a debugger command typed after `:`
+    fn run_command(&mut self, command: &str) {
+        self.running = false;
+        self.remember_memory();
+
+        self.message = match self.debugger.execute(command) {
+            Ok(Reply::Output(output)) => output,
+            Ok(Reply::Quit) => {
+                self.quit = true;
+                String::new()
+            }
+            Err(debugger_error) => format!("Error, {debugger_error}"),
+        };
+    }
+
+    /// Let `elapsed` time pass, executing instructions at the current speed while running
+    pub fn tick(&mut self, elapsed: Duration) {
+        if !self.running {
+            return;
+        }
+
+        self.pending += elapsed.as_nanos() * u128::from(self.speed);
+
+        let nanos_per_second = Duration::from_secs(1).as_nanos();
+        let count = self.pending / nanos_per_second;
+        self.pending %= nanos_per_second;
+
+        // A slow frame doesn't make up for more than one second of instructions
+        let count = u64::try_from(count).unwrap_or(u64::MAX).min(self.speed);
+
+        if count > 0 {
+            self.execute(count, false);
+        }
+    }
+
+    fn disassembly(&self) -> Vec<String> {
+        let pc = usize::from(self.debugger.emulator().pc());
+        let first = pc.saturating_sub(3).min(ROM_SIZE - DISASSEMBLY_ROWS);
+
+        (first..first + DISASSEMBLY_ROWS)
+            .map(|address| {
+                let address = u8::try_from(address).unwrap_or_default();
+                let breakpoint = if self.debugger.breakpoints().contains(&address) {
+                    '*'
+                } else {
+                    ' '
+                };
+                let current = usize::from(address) == pc;
+                let label = self.debugger.label_at(address).unwrap_or_default();
+
+                let line = format!(
+                    "{breakpoint}{} 0x{address:02X}  {label:<12} {}",
+                    if current { '>' } else { ' ' },
+                    self.debugger.instruction_at(address)
+                );
+                let line = format!("{line:<LEFT_WIDTH$.LEFT_WIDTH$}");
+
+                if current {
+                    format!("{HIGHLIGHT}{line}{NORMAL}")
+                } else {
+                    line
+                }
+            })
+            .collect()
+    }
+
+    fn registers(&self) -> Vec<String> {
+        let emulator = self.debugger.emulator();
+        let accumulator = emulator.accumulator();
+
+        let mut lines = vec![
+            format!("PC      0x{:02X}", emulator.pc()),
+            format!("ACC     0x{accumulator:02X} ({accumulator})"),
+            format!("Flags   {}", emulator.flags().letters()),
+            format!("Cycles  {}", emulator.cycles()),
+            String::new(),
+            format!(
+                "{}  {} instr/s",
+                if self.running { "Running" } else { "Paused " },
+                self.speed
+            ),
+            String::new(),
+        ];
+
+        lines.extend(emulator.bus().device_states().lines().map(str::to_owned));
+        lines
+    }
+
+    fn ram(&self) -> Vec<String> {
+        let memory = self.memory();
+
+        (0..RAM_ROWS)
+            .map(|row| {
+                let start = self.ram_start + row * 16;
+                let mut line = format!("0x{start:02X} ");
+
+                for (address, value) in memory.iter().enumerate().skip(start).take(16) {
+                    if *value == self.previous_ram[address] {
+                        let _ = write!(line, " {value:02X}");
+                    } else {
+                        let _ = write!(line, " {CHANGED}{value:02X}{NORMAL}");
+                    }
+                }
+
+                line
+            })
+            .collect()
+    }
+
+    /// Whole screen, `rows` lines without a final newline, the message area takes the free rows
+    pub fn render(&self, rows: usize) -> String {
+        let mut lines = vec![format!("nano_chip  {}", self.title)];
+
+        let registers = self.registers();
+        let disassembly = self.disassembly();
+
+        for row in 0..disassembly.len().max(registers.len()) {
+            let left = disassembly
+                .get(row)
+                .cloned()
+                .unwrap_or_else(|| " ".repeat(LEFT_WIDTH));
+            let right = registers.get(row).map_or("", String::as_str);
+            lines.push(format!("{left}  {right}").trim_end().to_owned());
+        }
+
+        lines.push(String::new());
+        lines.extend(self.ram());
+        lines.push(String::new());
+
+        let fixed = lines.len() + 1;
+        let message_rows = rows.saturating_sub(fixed).max(1);
+        let message = self.message.lines().collect::<Vec<_>>();
+        let shown = message.len().min(message_rows);
+
+        lines.extend(
+            message[message.len() - shown..]
+                .iter()
+                .map(|line| (*line).to_owned()),
+        );
+        lines.extend(std::iter::repeat_n(String::new(), message_rows - shown));
+
+        lines.push(
+            self.command
+                .as_ref()
+                .map_or_else(|| KEYS.to_owned(), |command| format!(":{command}_")),
+        );
+
+        lines.join("\n")
+    }
+}
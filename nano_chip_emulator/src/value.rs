@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use nano_chip_assembler::{parse_literal, to_byte};
+use nano_chip_isa::AddressingMode;
+
+/// Values of constants and labels, keyed by their name with its `$` or `:` prefix
+pub type Symbols = HashMap<String, u8>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValueError {
+    Invalid(String),
+    UnknownSymbol(String),
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(value) => write!(f, "`{value}` is not a valid 8 bits value"),
+            Self::UnknownSymbol(symbol) => write!(f, "unknown symbol `{symbol}`"),
+        }
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+/// Labels sorted by address, the first one in alphabetical order when several share an address
+pub fn labels(symbols: &Symbols) -> Vec<(u8, &str)> {
+    let mut labels = symbols
+        .iter()
+        .filter(|(name, _)| name.starts_with(':'))
+        .map(|(name, value)| (*value, name.as_str()))
+        .collect::<Vec<_>>();
+
+    labels.sort_unstable();
+    labels.dedup_by_key(|(address, _)| *address);
+    labels
+}
+
+/// First symbol, in alphabetical order, with this prefix and value
+fn symbol_at(symbols: &Symbols, prefix: char, value: u8) -> Option<&str> {
+    symbols
+        .iter()
+        .filter(|(name, symbol_value)| name.starts_with(prefix) && **symbol_value == value)
+        .map(|(name, _)| name.as_str())
+        .min()
+}
+
+/// First label, in alphabetical order, pointing to `address`
+pub fn label_at(symbols: &Symbols, address: u8) -> Option<&str> {
+    symbol_at(symbols, ':', address)
+}
+
+/// First constant, in alphabetical order, whose value is `value`
+pub fn constant_at(symbols: &Symbols, value: u8) -> Option<&str> {
+    symbol_at(symbols, '$', value)
+}
+
+/// Assembler syntax of a ROM word, with the label of branch targets and the constant naming RAM
+/// addresses when there is one, `None` for an unknown opcode
+pub fn format_instruction(word: u16, symbols: &Symbols) -> Option<String> {
+    let (info, operand) = nano_chip_isa::decode(word)?;
+    let name = info.mnemonic.name();
+
+    let symbol = match info.mode {
+        AddressingMode::Const if info.mnemonic.is_branch() => label_at(symbols, operand),
+        AddressingMode::Addr => constant_at(symbols, operand),
+        _ => None,
+    };
+
+    Some(match (symbol, info.mode) {
+        (Some(label), AddressingMode::Const) => format!("{name} {label}"),
+        (Some(constant), _) => format!("{name} [{constant}]"),
+        (None, _) => info.format(operand),
+    })
+}
+
+/// Parse a literal like the assembler does (`42`, `-1`, `0x2A`, `0b101010`, `'A'`) or a symbol
+/// (`$const`, `:label`) that fits in 8 bits
+pub fn parse_value(text: &str, symbols: &Symbols) -> Result<u8, ValueError> {
+    let text = text.trim();
+
+    if text.starts_with(['$', ':']) {
+        return symbols
+            .get(text)
+            .copied()
+            .ok_or_else(|| ValueError::UnknownSymbol(text.to_owned()));
+    }
+
+    parse_literal(text)
+        .and_then(Result::ok)
+        .and_then(to_byte)
+        .ok_or_else(|| ValueError::Invalid(text.to_owned()))
+}
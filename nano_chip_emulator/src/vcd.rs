@@ -0,0 +1,281 @@
+use std::io::{self, Write};
+
+use nano_chip_isa::{Flag, Flags};
+
+use crate::{NanoChipEmulator, StepRecord};
+
+/// Shortest clock period, the clock falls half a period after it rises and both must be at different
+/// timestamps
+pub const MIN_CLOCK_PERIOD: u64 = 2;
+
+/// Names and timing of the dumped signals, the defaults can be renamed to match a VHDL entity
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VcdConfig {
+    /// Clock period in nanoseconds, at least `MIN_CLOCK_PERIOD`
+    pub clock_period: u64,
+    /// Name of the module holding every signal
+    pub scope: String,
+    pub clock: String,
+    pub pc: String,
+    pub accumulator: String,
+    /// Flag signals in the ZCVN order
+    pub flags: [String; 4],
+    pub instruction: String,
+    /// RAM cells to dump and their signal name
+    pub ram_cells: Vec<(u8, String)>,
+}
+
+impl Default for VcdConfig {
+    fn default() -> Self {
+        Self {
+            clock_period: 20,
+            scope: "nano_chip".to_owned(),
+            clock: "clk".to_owned(),
+            pc: "pc".to_owned(),
+            accumulator: "accumulator".to_owned(),
+            flags: Flag::ALL.map(|flag| flag.name().to_lowercase()),
+            instruction: "instruction".to_owned(),
+            ram_cells: Vec::new(),
+        }
+    }
+}
+
+impl VcdConfig {
+    /// Rename a signal given by its default name (`clk`, `pc`, `accumulator`, `z`, `c`, `v`, `n`,
+    /// `instruction`) or the scope with `scope`, returns false if there is no such signal
+    pub fn rename(&mut self, signal: &str, name: &str) -> bool {
+        let target = match signal {
+            "scope" => &mut self.scope,
+            "clk" => &mut self.clock,
+            "pc" => &mut self.pc,
+            "accumulator" => &mut self.accumulator,
+            "instruction" => &mut self.instruction,
+            _ => match Flag::ALL
+                .iter()
+                .position(|flag| flag.name().eq_ignore_ascii_case(signal))
+            {
+                Some(index) => &mut self.flags[index],
+                None => return false,
+            },
+        };
+
+        name.clone_into(target);
+        true
+    }
+
+    /// Dump a RAM cell, named `ram_XX` after its address in hexadecimal unless a name is given
+    pub fn add_ram_cell(&mut self, address: u8, name: Option<&str>) {
+        let name = name.map_or_else(|| format!("ram_{address:02X}"), str::to_owned);
+        self.ram_cells.push((address, name));
+    }
+}
+
+/// Signal identifiers are made of the printable characters from `!` to `~`
+fn identifier(mut index: usize) -> String {
+    let mut identifier = String::new();
+
+    loop {
+        let digit = u8::try_from(index % 94).unwrap_or_default();
+        identifier.push(char::from(b'!' + digit));
+        index /= 94;
+
+        if index == 0 {
+            return identifier;
+        }
+    }
+}
+
+/// Values of the dumped signals at one point in time
+#[derive(Clone, PartialEq, Eq)]
+struct Signals {
+    pc: u8,
+    accumulator: u8,
+    flags: Flags,
+    instruction: u16,
+    ram_cells: Vec<u8>,
+}
+
+const CLOCK: usize = 0;
+const PC: usize = 1;
+const ACCUMULATOR: usize = 2;
+const FLAGS: usize = 3;
+const INSTRUCTION: usize = 7;
+const RAM_CELLS: usize = 8;
+
+/// Writes a Value Change Dump of the CPU, one clock period per executed instruction
+///
+/// At the rising edge of cycle `n` the program counter and instruction word of the instruction
+/// are shown, the accumulator, flags and RAM take their new values at the next rising edge
+pub struct VcdWriter<W: Write> {
+    config: VcdConfig,
+    output: W,
+    signals: Signals,
+    /// Time of the last `#` line, in nanoseconds
+    time: u64,
+    /// End of the last executed instruction
+    end: u64,
+}
+
+impl<W: Write> VcdWriter<W> {
+    /// Write the header and the current state of `emulator` as the initial values
+    pub fn new(config: VcdConfig, mut output: W, emulator: &NanoChipEmulator) -> io::Result<Self> {
+        writeln!(output, "$version nano_chip_emulator $end")?;
+        writeln!(output, "$timescale 1ns $end")?;
+        writeln!(output, "$scope module {} $end", config.scope)?;
+
+        let mut declare = |index, width, name: &str| {
+            writeln!(
+                output,
+                "$var wire {width} {} {name} $end",
+                identifier(index)
+            )
+        };
+
+        declare(CLOCK, 1, &config.clock)?;
+        declare(PC, 8, &config.pc)?;
+        declare(ACCUMULATOR, 8, &config.accumulator)?;
+        for (offset, name) in config.flags.iter().enumerate() {
+            declare(FLAGS + offset, 1, name)?;
+        }
+        declare(INSTRUCTION, 14, &config.instruction)?;
+        for (offset, (_, name)) in config.ram_cells.iter().enumerate() {
+            declare(RAM_CELLS + offset, 8, name)?;
+        }
+
+        writeln!(output, "$upscope $end")?;
+        writeln!(output, "$enddefinitions $end")?;
+
+        let signals = Signals {
+            pc: emulator.pc(),
+            accumulator: emulator.accumulator(),
+            flags: emulator.flags(),
+            instruction: emulator.current_instruction(),
+            ram_cells: config
+                .ram_cells
+                .iter()
+                .map(|(address, _)| emulator.read_ram(*address))
+                .collect(),
+        };
+
+        let time = emulator.cycles() * config.clock_period;
+
+        let mut writer = Self {
+            config,
+            output,
+            signals: signals.clone(),
+            time,
+            end: time,
+        };
+
+        writeln!(writer.output, "#{time}")?;
+        writeln!(writer.output, "$dumpvars")?;
+        writer.write_bit(CLOCK, false)?;
+        writer.write_changes(None, &signals)?;
+        writeln!(writer.output, "$end")?;
+
+        Ok(writer)
+    }
+
+    fn write_bit(&mut self, index: usize, value: bool) -> io::Result<()> {
+        writeln!(self.output, "{}{}", u8::from(value), identifier(index))
+    }
+
+    fn write_vector(&mut self, index: usize, width: usize, value: u16) -> io::Result<()> {
+        writeln!(self.output, "b{value:0width$b} {}", identifier(index))
+    }
+
+    /// Write the signals that differ from `previous`, every signal when there is none
+    fn write_changes(&mut self, previous: Option<&Signals>, signals: &Signals) -> io::Result<()> {
+        let changed =
+            |value: fn(&Signals) -> u16| previous.is_none_or(|p| value(p) != value(signals));
+
+        if changed(|s| u16::from(s.pc)) {
+            self.write_vector(PC, 8, u16::from(signals.pc))?;
+        }
+
+        if changed(|s| u16::from(s.accumulator)) {
+            self.write_vector(ACCUMULATOR, 8, u16::from(signals.accumulator))?;
+        }
+
+        for (offset, flag) in Flag::ALL.into_iter().enumerate() {
+            if previous.is_none_or(|p| p.flags.get(flag) != signals.flags.get(flag)) {
+                self.write_bit(FLAGS + offset, signals.flags.get(flag))?;
+            }
+        }
+
+        if changed(|s| s.instruction) {
+            self.write_vector(
+                INSTRUCTION,
+                14,
+                signals.instruction & nano_chip_isa::WORD_MASK,
+            )?;
+        }
+
+        for (offset, value) in signals.ram_cells.iter().enumerate() {
+            if previous.is_none_or(|p| p.ram_cells[offset] != *value) {
+                self.write_vector(RAM_CELLS + offset, 8, u16::from(*value))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move to `time` and write the signals that changed
+    fn change(&mut self, time: u64, clock: Option<bool>, signals: &Signals) -> io::Result<()> {
+        if clock.is_none() && *signals == self.signals {
+            return Ok(());
+        }
+
+        if time != self.time {
+            writeln!(self.output, "#{time}")?;
+            self.time = time;
+        }
+
+        if let Some(clock) = clock {
+            self.write_bit(CLOCK, clock)?;
+        }
+
+        let previous = std::mem::replace(&mut self.signals, signals.clone());
+        self.write_changes(Some(&previous), signals)
+    }
+
+    /// Add the clock period of an executed instruction
+    pub fn write(&mut self, step: &StepRecord) -> io::Result<()> {
+        let period = self.config.clock_period;
+        let start = step.cycle * period;
+
+        let mut during = self.signals.clone();
+        during.pc = step.pc;
+        during.instruction = step.word;
+        during.accumulator = step.accumulator_before;
+        during.flags = step.flags_before;
+        self.change(start, Some(true), &during)?;
+
+        self.change(start + period / 2, Some(false), &self.signals.clone())?;
+
+        let mut after = self.signals.clone();
+        after.accumulator = step.accumulator_after;
+        after.flags = step.flags_after;
+
+        if let Some(write) = &step.ram_write {
+            for (value, (address, _)) in after.ram_cells.iter_mut().zip(&self.config.ram_cells) {
+                if *address == write.address {
+                    *value = write.new_value;
+                }
+            }
+        }
+
+        self.end = start + period;
+        self.change(self.end, None, &after)
+    }
+
+    /// Write the final timestamp so that the last instruction is shown over a whole period
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.end > self.time {
+            writeln!(self.output, "#{}", self.end)?;
+        }
+
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
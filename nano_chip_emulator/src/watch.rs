@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use nano_chip_isa::Flag;
+
+use crate::value::{parse_value, Symbols, ValueError};
+use crate::{NanoChipEmulator, Target};
+
+/// Tokens of two characters, checked before the one character ones
+const OPERATORS: [&str; 6] = ["&&", "||", "==", "!=", "<=", ">="];
+
+/// Characters that end a value in a condition
+const SEPARATORS: &str = "()!&|=<>";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConditionError {
+    Value(ValueError),
+    /// Holds what was expected
+    Syntax(String),
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(value_error) => write!(f, "{value_error}"),
+            Self::Syntax(message) => write!(f, "invalid condition, {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    Target(Target),
+    Value(u8),
+}
+
+impl Operand {
+    fn read(self, emulator: &NanoChipEmulator) -> u8 {
+        match self {
+            Self::Target(target) => target.read(emulator),
+            Self::Value(value) => value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    /// Comparison from its operator token : `==`, `!=`, `<`, `<=`, `>` or `>=`
+    fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "==" => Some(Self::Equal),
+            "!=" => Some(Self::NotEqual),
+            "<" => Some(Self::Less),
+            "<=" => Some(Self::LessOrEqual),
+            ">" => Some(Self::Greater),
+            ">=" => Some(Self::GreaterOrEqual),
+            _ => None,
+        }
+    }
+
+    const fn compare(self, left: u8, right: u8) -> bool {
+        match self {
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::Less => left < right,
+            Self::LessOrEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Expression {
+    /// True when the operand isn't 0
+    Operand(Operand),
+    /// Two operands and the comparison operator between them
+    Compare(Operand, Comparison, Operand),
+    Not(Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+}
+
+impl Expression {
+    fn evaluate(&self, emulator: &NanoChipEmulator) -> bool {
+        match self {
+            Self::Operand(operand) => operand.read(emulator) != 0,
+            Self::Compare(left, comparison, right) => {
+                comparison.compare(left.read(emulator), right.read(emulator))
+            }
+            Self::Not(expression) => !expression.evaluate(emulator),
+            Self::And(left, right) => left.evaluate(emulator) && right.evaluate(emulator),
+            Self::Or(left, right) => left.evaluate(emulator) || right.evaluate(emulator),
+        }
+    }
+}
+
+/// Split a condition into operators, parentheses and values
+fn tokenize(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+
+    while let Some(first) = rest.chars().next() {
+        let length = if OPERATORS.iter().any(|operator| rest.starts_with(operator)) {
+            2
+        } else if SEPARATORS.contains(first) {
+            first.len_utf8()
+        } else {
+            rest.find(|c: char| c.is_whitespace() || SEPARATORS.contains(c))
+                .unwrap_or(rest.len())
+        };
+
+        tokens.push(&rest[..length]);
+        rest = rest[length..].trim_start();
+    }
+
+    tokens
+}
+
+/// Recursive descent parser, `||` binds less than `&&` which binds less than `!`
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+    symbols: &'a Symbols,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expression, ConditionError> {
+        let mut expression = self.and()?;
+
+        while self.peek() == Some("||") {
+            self.position += 1;
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, ConditionError> {
+        let mut expression = self.unary()?;
+
+        while self.peek() == Some("&&") {
+            self.position += 1;
+            expression = Expression::And(Box::new(expression), Box::new(self.unary()?));
+        }
+
+        Ok(expression)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ConditionError> {
+        match self.peek() {
+            Some("!") => {
+                self.position += 1;
+                Ok(Expression::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                self.position += 1;
+                let expression = self.or()?;
+
+                match self.next() {
+                    Some(")") => Ok(expression),
+                    _ => Err(ConditionError::Syntax("expected `)`".to_owned())),
+                }
+            }
+            _ => {
+                let left = self.operand()?;
+
+                let Some(comparison) = self.peek().and_then(Comparison::from_token) else {
+                    return Ok(Expression::Operand(left));
+                };
+
+                self.position += 1;
+                Ok(Expression::Compare(left, comparison, self.operand()?))
+            }
+        }
+    }
+
+    /// `acc`, `pc`, a flag, a RAM cell like `[0x10]` or `ram[$counter]`, or a value
+    fn operand(&mut self) -> Result<Operand, ConditionError> {
+        let token = self
+            .next()
+            .ok_or_else(|| ConditionError::Syntax("expected a value at the end".to_owned()))?;
+
+        if token.starts_with(|c| SEPARATORS.contains(c)) {
+            return Err(ConditionError::Syntax(format!(
+                "expected a value before `{token}`"
+            )));
+        }
+
+        let value = |text: &str| parse_value(text, self.symbols).map_err(ConditionError::Value);
+
+        if let Some(address) = token
+            .strip_prefix("ram[")
+            .or_else(|| token.strip_prefix('['))
+            .and_then(|address| address.strip_suffix(']'))
+        {
+            return Ok(Operand::Target(Target::Ram(value(address)?)));
+        }
+
+        let target = match token.to_lowercase().as_str() {
+            "acc" => Target::Accumulator,
+            "pc" => Target::ProgramCounter,
+            name => match Flag::ALL
+                .into_iter()
+                .find(|flag| flag.name().eq_ignore_ascii_case(name))
+            {
+                Some(flag) => Target::Flag(flag),
+                None => return Ok(Operand::Value(value(token)?)),
+            },
+        };
+
+        Ok(Operand::Target(target))
+    }
+}
+
+/// Expression on the CPU state, like `acc == 0 && c` or `[$counter] >= 10 || !z`
+///
+/// Operands are `acc`, `pc`, the flags `z`, `c`, `v` and `n`, RAM cells like `[0x10]` and values,
+/// compared with `==`, `!=`, `<`, `<=`, `>` and `>=` as unsigned bytes. An operand alone is true
+/// when it isn't 0, and conditions combine with `!`, `&&`, `||` and parentheses
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Condition {
+    text: String,
+    expression: Expression,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl Condition {
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Self, ConditionError> {
+        let mut parser = Parser {
+            tokens: tokenize(text),
+            position: 0,
+            symbols,
+        };
+
+        let expression = parser.or()?;
+
+        if let Some(token) = parser.peek() {
+            return Err(ConditionError::Syntax(format!("unexpected `{token}`")));
+        }
+
+        Ok(Self {
+            text: text.trim().to_owned(),
+            expression,
+        })
+    }
+
+    pub fn evaluate(&self, emulator: &NanoChipEmulator) -> bool {
+        self.expression.evaluate(emulator)
+    }
+}
+
+/// What a watchpoint waits for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    /// An instruction writes the RAM cell, even with the value it already holds
+    Write(u8),
+    /// The condition turns true, it must become false again to trigger another time
+    Condition(Condition),
+}
+
+impl WatchKind {
+    /// A RAM cell alone, like `[0x10]`, watches its writes, anything else is a condition
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Self, ConditionError> {
+        let condition = Condition::parse(text, symbols)?;
+
+        Ok(match condition.expression {
+            Expression::Operand(Operand::Target(Target::Ram(address))) => Self::Write(address),
+            _ => Self::Condition(condition),
+        })
+    }
+}
+
+impl fmt::Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Write(address) => write!(f, "[0x{address:02X}] written"),
+            Self::Condition(condition) => write!(f, "{condition}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    /// Number of times the watchpoint triggered, ignored ones included
+    pub hits: u64,
+    /// Number of coming hits that don't stop execution
+    pub ignore: u64,
+    /// Value of the condition at the last check
+    was_true: bool,
+}
+
+/// Watchpoints checked after each instruction, numbered from 1 in the order they are added
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Watchpoints {
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    last_id: usize,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a watchpoint and return its number
+    pub fn add(&mut self, kind: WatchKind) -> usize {
+        self.last_id += 1;
+        self.watchpoints.insert(
+            self.last_id,
+            Watchpoint {
+                kind,
+                hits: 0,
+                ignore: 0,
+                was_true: false,
+            },
+        );
+
+        self.last_id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Watchpoint> {
+        self.watchpoints.remove(&id)
+    }
+
+    /// Remove every watchpoint and return how many there were, numbering goes on
+    pub fn clear(&mut self) -> usize {
+        let count = self.watchpoints.len();
+        self.watchpoints.clear();
+        count
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Watchpoint> {
+        self.watchpoints.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Watchpoint> {
+        self.watchpoints.get_mut(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints
+            .iter()
+            .map(|(id, watchpoint)| (*id, watchpoint))
+    }
+
+    /// Take the current state as the starting point of the conditions, so that a condition already
+    /// true doesn't trigger
+    ///
+    /// Call it before executing instructions, and whenever the state changed without `tick`
+    pub fn sync(&mut self, emulator: &NanoChipEmulator) {
+        for watchpoint in self.watchpoints.values_mut() {
+            if let WatchKind::Condition(condition) = &watchpoint.kind {
+                watchpoint.was_true = condition.evaluate(emulator);
+            }
+        }
+    }
+
+    /// Check every watchpoint after a `tick` and count their hits, returns the first one that
+    /// triggered and has no hit left to ignore
+    pub fn check(&mut self, emulator: &NanoChipEmulator) -> Option<usize> {
+        let written = emulator
+            .last_step()
+            .and_then(|step| step.ram_write)
+            .map(|write| write.address);
+
+        let mut stop = None;
+
+        for (id, watchpoint) in &mut self.watchpoints {
+            let triggered = match &watchpoint.kind {
+                WatchKind::Write(address) => written == Some(*address),
+                WatchKind::Condition(condition) => {
+                    let is_true = condition.evaluate(emulator);
+                    let turned_true = is_true && !watchpoint.was_true;
+                    watchpoint.was_true = is_true;
+                    turned_true
+                }
+            };
+
+            if !triggered {
+                continue;
+            }
+
+            watchpoint.hits += 1;
+
+            if watchpoint.ignore > 0 {
+                watchpoint.ignore -= 1;
+            } else if stop.is_none() {
+                stop = Some(*id);
+            }
+        }
+
+        stop
+    }
+}
@@ -0,0 +1,365 @@
+//! Description of the nano chip instruction set
+//!
+//! Every instruction is described once in [`INSTRUCTIONS`], the assembler, the emulator and the
+//! ROM generator all read opcodes, addressing modes and flags from this table.
+
+#![warn(clippy::pedantic)]
+#![warn(clippy::nursery)]
+#![allow(clippy::struct_excessive_bools)]
+#![allow(clippy::fn_params_excessive_bools)]
+#![allow(clippy::must_use_candidate)]
+
+mod tests;
+
+/// Number of words in the program ROM
+pub const ROM_SIZE: usize = 256;
+
+/// Number of bytes in the RAM
+pub const RAM_SIZE: usize = 256;
+
+/// Instructions are 14 bits wide : a 6 bits opcode followed by an 8 bits operand
+pub const WORD_MASK: u16 = 0b0011_1111_1111_1111;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mnemonic {
+    St,
+    Ld,
+    And,
+    Or,
+    Xor,
+    Rol,
+    Ror,
+    Add,
+    Adc,
+    Neg,
+    Inc,
+    Dec,
+    Setc,
+    Clrc,
+    Trfnc,
+    Bz0,
+    Bz1,
+    Bc0,
+    Bc1,
+    Bv0,
+    Bv1,
+    Bn0,
+    Bn1,
+    Bra,
+    Nop,
+}
+
+impl Mnemonic {
+    pub const ALL: [Self; 25] = [
+        Self::St,
+        Self::Ld,
+        Self::And,
+        Self::Or,
+        Self::Xor,
+        Self::Rol,
+        Self::Ror,
+        Self::Add,
+        Self::Adc,
+        Self::Neg,
+        Self::Inc,
+        Self::Dec,
+        Self::Setc,
+        Self::Clrc,
+        Self::Trfnc,
+        Self::Bz0,
+        Self::Bz1,
+        Self::Bc0,
+        Self::Bc1,
+        Self::Bv0,
+        Self::Bv1,
+        Self::Bn0,
+        Self::Bn1,
+        Self::Bra,
+        Self::Nop,
+    ];
+
+    /// Name of the instruction as written in assembly
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::St => "ST",
+            Self::Ld => "LD",
+            Self::And => "AND",
+            Self::Or => "OR",
+            Self::Xor => "XOR",
+            Self::Rol => "ROL",
+            Self::Ror => "ROR",
+            Self::Add => "ADD",
+            Self::Adc => "ADC",
+            Self::Neg => "NEG",
+            Self::Inc => "INC",
+            Self::Dec => "DEC",
+            Self::Setc => "SETC",
+            Self::Clrc => "CLRC",
+            Self::Trfnc => "TRFNC",
+            Self::Bz0 => "BZ0",
+            Self::Bz1 => "BZ1",
+            Self::Bc0 => "BC0",
+            Self::Bc1 => "BC1",
+            Self::Bv0 => "BV0",
+            Self::Bv1 => "BV1",
+            Self::Bn0 => "BN0",
+            Self::Bn1 => "BN1",
+            Self::Bra => "BRA",
+            Self::Nop => "NOP",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mnemonic| mnemonic.name() == name)
+    }
+
+    /// Flag tested by a conditional branch and the value that makes the branch taken
+    pub const fn branch_condition(self) -> Option<(Flag, bool)> {
+        match self {
+            Self::Bz0 => Some((Flag::Z, false)),
+            Self::Bz1 => Some((Flag::Z, true)),
+            Self::Bc0 => Some((Flag::C, false)),
+            Self::Bc1 => Some((Flag::C, true)),
+            Self::Bv0 => Some((Flag::V, false)),
+            Self::Bv1 => Some((Flag::V, true)),
+            Self::Bn0 => Some((Flag::N, false)),
+            Self::Bn1 => Some((Flag::N, true)),
+            _ => None,
+        }
+    }
+
+    /// True for instructions whose operand is a ROM address
+    pub const fn is_branch(self) -> bool {
+        matches!(self, Self::Bra) || self.branch_condition().is_some()
+    }
+}
+
+/// How the operand of an instruction is interpreted
+///
+/// Branch targets are ROM addresses written without brackets, they use [`AddressingMode::Const`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressingMode {
+    /// No parameter, the operand is ignored
+    None,
+    /// `ACC` parameter, the operand is ignored
+    Acc,
+    /// Direct value, the operand is the value itself
+    Const,
+    /// Indirect value written `[n]`, the operand is a RAM address
+    Addr,
+}
+
+impl AddressingMode {
+    /// Human readable description used in error messages
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::None => "no parameter",
+            Self::Acc => "acc",
+            Self::Const => "a constant",
+            Self::Addr => "an address",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Flag {
+    Z,
+    C,
+    V,
+    N,
+}
+
+impl Flag {
+    pub const ALL: [Self; 4] = [Self::Z, Self::C, Self::V, Self::N];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Z => "Z",
+            Self::C => "C",
+            Self::V => "V",
+            Self::N => "N",
+        }
+    }
+}
+
+/// Value of the four flags, also used to describe which flags an instruction changes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flags {
+    pub z: bool,
+    pub c: bool,
+    pub v: bool,
+    pub n: bool,
+}
+
+impl Flags {
+    pub const NONE: Self = Self::new(false, false, false, false);
+    pub const C: Self = Self::new(false, true, false, false);
+    pub const ZN: Self = Self::new(true, false, false, true);
+    pub const CZN: Self = Self::new(true, true, false, true);
+    pub const ZCVN: Self = Self::new(true, true, true, true);
+
+    pub const fn new(z: bool, c: bool, v: bool, n: bool) -> Self {
+        Self { z, c, v, n }
+    }
+
+    pub const fn get(self, flag: Flag) -> bool {
+        match flag {
+            Flag::Z => self.z,
+            Flag::C => self.c,
+            Flag::V => self.v,
+            Flag::N => self.n,
+        }
+    }
+
+    pub const fn set(&mut self, flag: Flag, value: bool) {
+        match flag {
+            Flag::Z => self.z = value,
+            Flag::C => self.c = value,
+            Flag::V => self.v = value,
+            Flag::N => self.n = value,
+        }
+    }
+
+    /// Name of each set flag in the ZCVN order, `-` for the cleared ones, like `Z--N`
+    pub fn letters(self) -> String {
+        Flag::ALL
+            .iter()
+            .map(|&flag| if self.get(flag) { flag.name() } else { "-" })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionInfo {
+    pub mnemonic: Mnemonic,
+    pub opcode: u8,
+    pub mode: AddressingMode,
+    /// Flags written by the instruction
+    pub flags: Flags,
+}
+
+impl InstructionInfo {
+    const fn new(mnemonic: Mnemonic, opcode: u8, mode: AddressingMode, flags: Flags) -> Self {
+        Self {
+            mnemonic,
+            opcode,
+            mode,
+            flags,
+        }
+    }
+
+    /// Build the ROM word for this instruction, the operand is ignored when the mode has none
+    pub const fn encode(&self, operand: u8) -> u16 {
+        match self.mode {
+            AddressingMode::None | AddressingMode::Acc => (self.opcode as u16) << 8,
+            AddressingMode::Const | AddressingMode::Addr => {
+                (self.opcode as u16) << 8 | operand as u16
+            }
+        }
+    }
+
+    /// Assembler syntax of this instruction, like `LD [0x2A]` or `ROL ACC`
+    pub fn format(&self, operand: u8) -> String {
+        let name = self.mnemonic.name();
+
+        match self.mode {
+            AddressingMode::None => name.to_owned(),
+            _ => format!("{name} {}", self.format_operand(operand)),
+        }
+    }
+
+    /// Assembler syntax of the operand alone, like `[0x2A]`, empty when the instruction has none
+    pub fn format_operand(&self, operand: u8) -> String {
+        match self.mode {
+            AddressingMode::None => String::new(),
+            AddressingMode::Acc => "ACC".to_owned(),
+            AddressingMode::Const => format!("0x{operand:02X}"),
+            AddressingMode::Addr => format!("[0x{operand:02X}]"),
+        }
+    }
+}
+
+/// Every instruction understood by the CPU
+pub const INSTRUCTIONS: [InstructionInfo; 35] = {
+    use AddressingMode::{Acc, Addr, Const, None};
+
+    [
+        InstructionInfo::new(Mnemonic::St, 0x01, Addr, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Ld, 0x02, Const, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Ld, 0x03, Addr, Flags::ZN),
+        InstructionInfo::new(Mnemonic::And, 0x04, Const, Flags::ZN),
+        InstructionInfo::new(Mnemonic::And, 0x05, Addr, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Or, 0x06, Const, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Or, 0x07, Addr, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Xor, 0x08, Const, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Xor, 0x09, Addr, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Rol, 0x0A, Acc, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Ror, 0x0B, Acc, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Add, 0x0C, Const, Flags::ZCVN),
+        InstructionInfo::new(Mnemonic::Add, 0x0D, Addr, Flags::ZCVN),
+        InstructionInfo::new(Mnemonic::Adc, 0x0E, Const, Flags::ZCVN),
+        InstructionInfo::new(Mnemonic::Adc, 0x0F, Addr, Flags::ZCVN),
+        InstructionInfo::new(Mnemonic::Neg, 0x10, Acc, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Neg, 0x11, Const, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Neg, 0x12, Addr, Flags::ZN),
+        InstructionInfo::new(Mnemonic::Inc, 0x13, Acc, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Inc, 0x14, Addr, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Dec, 0x15, Acc, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Dec, 0x16, Addr, Flags::CZN),
+        InstructionInfo::new(Mnemonic::Setc, 0x17, None, Flags::C),
+        InstructionInfo::new(Mnemonic::Clrc, 0x18, None, Flags::C),
+        InstructionInfo::new(Mnemonic::Trfnc, 0x19, None, Flags::C),
+        InstructionInfo::new(Mnemonic::Bz0, 0x1A, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bz1, 0x1B, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bc0, 0x1C, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bc1, 0x1D, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bv0, 0x1E, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bv1, 0x1F, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bn0, 0x20, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bn1, 0x21, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Bra, 0x22, Const, Flags::NONE),
+        InstructionInfo::new(Mnemonic::Nop, 0x3F, None, Flags::NONE),
+    ]
+};
+
+pub fn from_opcode(opcode: u8) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS.iter().find(|info| info.opcode == opcode)
+}
+
+/// Find the variant of an instruction using the given addressing mode
+pub fn lookup(mnemonic: Mnemonic, mode: AddressingMode) -> Option<&'static InstructionInfo> {
+    INSTRUCTIONS
+        .iter()
+        .find(|info| info.mnemonic == mnemonic && info.mode == mode)
+}
+
+/// Every variant of an instruction
+pub fn variants(mnemonic: Mnemonic) -> impl Iterator<Item = &'static InstructionInfo> {
+    INSTRUCTIONS
+        .iter()
+        .filter(move |info| info.mnemonic == mnemonic)
+}
+
+/// Split a ROM word in its instruction and operand, returns None for unknown opcodes
+pub fn decode(word: u16) -> Option<(&'static InstructionInfo, u8)> {
+    let opcode = ((word & WORD_MASK) >> 8) as u8;
+    let operand = (word & 0xFF) as u8;
+
+    from_opcode(opcode).map(|info| (info, operand))
+}
+
+/// FNV-1a hash of a ROM holding `words` from address 0 and zeros after them, identifies a program
+pub fn rom_hash(words: &[u16]) -> u64 {
+    let padding = std::iter::repeat_n(0, ROM_SIZE.saturating_sub(words.len()));
+
+    words
+        .iter()
+        .copied()
+        .chain(padding)
+        .flat_map(u16::to_be_bytes)
+        .fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01B3)
+        })
+}
@@ -0,0 +1,47 @@
+#![cfg(test)]
+
+use crate::{decode, lookup, AddressingMode, Flags, Mnemonic, INSTRUCTIONS};
+
+#[test]
+fn test_opcodes_are_unique() {
+    for (i, a) in INSTRUCTIONS.iter().enumerate() {
+        for b in &INSTRUCTIONS[i + 1..] {
+            assert_ne!(a.opcode, b.opcode);
+            assert!(a.mnemonic != b.mnemonic || a.mode != b.mode);
+        }
+    }
+}
+
+#[test]
+fn test_every_mnemonic_has_a_variant() {
+    for mnemonic in Mnemonic::ALL {
+        assert!(INSTRUCTIONS.iter().any(|info| info.mnemonic == mnemonic));
+        assert_eq!(Mnemonic::from_name(mnemonic.name()), Some(mnemonic));
+    }
+}
+
+#[test]
+fn test_encode_decode() {
+    let ld_addr = lookup(Mnemonic::Ld, AddressingMode::Addr).unwrap();
+    assert_eq!(ld_addr.encode(42), 0x032A);
+    assert_eq!(decode(0x032A), Some((ld_addr, 42)));
+
+    let rol = lookup(Mnemonic::Rol, AddressingMode::Acc).unwrap();
+    assert_eq!(rol.encode(42), 0x0A00);
+
+    assert_eq!(ld_addr.format(42), "LD [0x2A]");
+    assert_eq!(rol.format(0), "ROL ACC");
+    assert_eq!(ld_addr.format_operand(42), "[0x2A]");
+
+    let nop = lookup(Mnemonic::Nop, AddressingMode::None).unwrap();
+    assert_eq!(nop.format_operand(42), "");
+
+    assert_eq!(decode(0x2300), None);
+}
+
+#[test]
+fn test_flag_letters() {
+    assert_eq!(Flags::NONE.letters(), "----");
+    assert_eq!(Flags::ZN.letters(), "Z--N");
+    assert_eq!(Flags::ZCVN.letters(), "ZCVN");
+}
@@ -1,24 +1,37 @@
-fn main() {
-    if let Some(input_file) = std::env::args().nth(1) {
-        match std::fs::read(input_file) {
-            Ok(raw_binary) => {
-                let mut vhdl_str = String::new();
+use nano_chip_isa::{AddressingMode, Mnemonic, WORD_MASK};
 
-                for u16index in 0..(raw_binary.len() / 2) {
-                    let mut instruction =
-                        (raw_binary[u16index] as u16) << 8 | raw_binary[u16index + 1] as u16;
+mod tests;
 
-                    instruction &= 0b0011_1111_1111_1111; // Mask so that the instruction is on 14 bits, (opcodes are limited to 6 bits)
+/// VHDL `with ... select` choices of a ROM holding the program, each word is two bytes big endian
+fn vhdl_rom(raw_binary: &[u8]) -> String {
+    let mut vhdl_str = String::new();
 
-                    vhdl_str.push_str(&format!(
-                        "\"{:014b}\" when \"{:08b}\",\n",
-                        instruction, u16index as u8
-                    ));
-                }
+    for u16index in 0..(raw_binary.len() / 2) {
+        let mut instruction =
+            (raw_binary[u16index * 2] as u16) << 8 | raw_binary[u16index * 2 + 1] as u16;
+
+        instruction &= WORD_MASK; // Mask so that the instruction is on 14 bits, (opcodes are limited to 6 bits)
+
+        vhdl_str.push_str(&format!(
+            "\"{:014b}\" when \"{:08b}\",\n",
+            instruction, u16index as u8
+        ));
+    }
 
-                vhdl_str.push_str("\"10001011111111\" when others;\n");
+    // Unused ROM words branch to the last address
+    let others = nano_chip_isa::lookup(Mnemonic::Bra, AddressingMode::Const)
+        .unwrap()
+        .encode(0xFF);
 
-                println!("{vhdl_str}");
+    vhdl_str.push_str(&format!("\"{others:014b}\" when others;\n"));
+    vhdl_str
+}
+
+fn main() {
+    if let Some(input_file) = std::env::args().nth(1) {
+        match std::fs::read(input_file) {
+            Ok(raw_binary) => {
+                println!("{}", vhdl_rom(&raw_binary));
             }
             Err(errmsg) => {
                 println!("Error, can't read input file : {errmsg}");
@@ -0,0 +1,16 @@
+#![cfg(test)]
+
+use crate::vhdl_rom;
+
+#[test]
+fn test_vhdl_rom() {
+    // LD 42, then NOP (0x3F00) with the two bits above the 14 bits of a word set
+    let vhdl = vhdl_rom(&[0x02, 0x2A, 0xFF, 0x00]);
+
+    assert_eq!(
+        vhdl,
+        "\"00001000101010\" when \"00000000\",\n\
+         \"11111100000000\" when \"00000001\",\n\
+         \"10001011111111\" when others;\n"
+    );
+}